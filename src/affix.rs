@@ -1,32 +1,90 @@
+use crate::compound::{CompoundOptions, CompoundRule, DEFAULT_COMPOUND_MIN};
+use crate::condition::Condition;
+use crate::conversion::ConversionTable;
+use crate::diagnostics::{field_column, Diagnostic, DiagnosticCode, Diagnostics, ParseMode, Source};
+use crate::encoding;
 use crate::error::{Result, RunmunchError};
-use regex::Regex;
+use crate::suggest::SuggestOptions;
 use std::collections::HashMap;
 use std::fs;
 use std::path::Path;
 
-#[derive(Debug, Clone, PartialEq)]
+/// Directives of hunspell's affix file format, including those this crate does not use.
+const KNOWN_DIRECTIVES: &[&str] = &[
+    "AF", "AM", "BREAK", "CHECKCOMPOUNDCASE", "CHECKCOMPOUNDDUP", "CHECKCOMPOUNDPATTERN",
+    "CHECKCOMPOUNDREP", "CHECKCOMPOUNDTRIPLE", "CHECKSHARPS", "CIRCUMFIX", "COMPLEXPREFIXES",
+    "COMPOUNDBEGIN", "COMPOUNDEND", "COMPOUNDFLAG", "COMPOUNDFORBIDFLAG", "COMPOUNDLAST",
+    "COMPOUNDMIDDLE", "COMPOUNDMIN", "COMPOUNDMORESUFFIXES", "COMPOUNDPERMITFLAG", "COMPOUNDROOT",
+    "COMPOUNDRULE", "COMPOUNDSYLLABLE", "COMPOUNDWORDMAX", "FLAG", "FORBIDDENWORD", "FORBIDWARN",
+    "FORCEUCASE", "FULLSTRIP", "HOME", "ICONV", "IGNORE", "KEEPCASE", "KEY", "LANG", "LEMMA_PRESENT",
+    "MAP", "MAXCPDSUGS", "MAXDIFF", "MAXNGRAMSUGS", "NAME", "NEEDAFFIX", "NOSPLITSUGS", "NOSUGGEST",
+    "OCONV", "ONLYINCOMPOUND", "ONLYMAXDIFF", "PFX", "PHONE", "PSEUDOROOT", "REP", "SET", "SFX",
+    "SIMPLIFIEDTRIPLE", "SUBSTANDARD", "SUGSWITHDOTS", "SYLLABLENUM", "TRY", "VERSION", "WARN",
+    "WORDCHARS",
+];
+
+/// Whether the header line of each counted table has been seen.
+#[derive(Default)]
+struct TableHeaders {
+    compound_rule: bool,
+    flag_alias: bool,
+    morph_alias: bool,
+    map: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum AffixType {
     Prefix,
     Suffix,
 }
 
-#[derive(Debug, Clone)]
+/// Entry-status flags (`FORBIDDENWORD`, `NOSUGGEST`, `WARN`, `SUBSTANDARD`) carried by a word form,
+/// collected from its root entry and the continuation classes of the rules applied to it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct WordStatus {
+    pub forbidden: bool,
+    pub no_suggest: bool,
+    pub warn: bool,
+    pub substandard: bool,
+    /// `ONLYINCOMPOUND`: the form may only appear inside compounds.
+    pub only_in_compound: bool,
+}
+
+impl WordStatus {
+    /// Whether the form may be output as a word on its own: it is neither forbidden nor
+    /// restricted to compounds.
+    pub fn is_standalone_word(&self) -> bool {
+        !self.forbidden && !self.only_in_compound
+    }
+
+    pub fn merge(self, other: WordStatus) -> WordStatus {
+        WordStatus {
+            forbidden: self.forbidden || other.forbidden,
+            no_suggest: self.no_suggest || other.no_suggest,
+            warn: self.warn || other.warn,
+            substandard: self.substandard || other.substandard,
+            only_in_compound: self.only_in_compound || other.only_in_compound,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct AffixRule {
     pub flag: String,
     pub cross_product: bool,
     pub strip: String,
     pub affix: String,
-    pub condition: Option<Regex>,
+    pub condition: Condition,
     pub conditions_raw: String,
+    /// Flags of the affix classes that may be applied on top of this rule (`SFX A 0 ing/B .`).
+    pub continuation: Vec<String>,
+    /// Morphological fields following the condition (`po:noun is:gen_sg`), or an `AM` alias number.
+    pub morph: Vec<String>,
 }
 
 impl AffixRule {
-    fn new(flag: String, cross_product: bool, strip: String, affix: String, condition_str: String) -> Result<Self> {
-        let condition = if condition_str == "." || condition_str.is_empty() {
-            None
-        } else {
-            Some(Self::parse_condition(&condition_str)?)
-        };
+    fn new(flag: String, cross_product: bool, strip: String, affix: String, condition_str: String, continuation: Vec<String>, morph: Vec<String>) -> Result<Self> {
+        let condition = Condition::parse(&condition_str)?;
 
         Ok(AffixRule {
             flag,
@@ -35,103 +93,26 @@ impl AffixRule {
             affix,
             condition,
             conditions_raw: condition_str,
+            continuation,
+            morph,
         })
     }
 
-    fn parse_condition(condition_str: &str) -> Result<Regex> {
-        let mut regex_str = String::new();
-        let chars: Vec<char> = condition_str.chars().collect();
-        let mut i = 0;
-
-        while i < chars.len() {
-            match chars[i] {
-                '[' => {
-                    let mut bracket_content = String::new();
-                    i += 1;
-                    
-                    let mut negate = false;
-                    if i < chars.len() && chars[i] == '^' {
-                        negate = true;
-                        i += 1;
-                    }
-
-                    while i < chars.len() && chars[i] != ']' {
-                        bracket_content.push(chars[i]);
-                        i += 1;
-                    }
-
-                    if i >= chars.len() {
-                        return Err(RunmunchError::InvalidAffix("Unclosed bracket in condition".to_string()));
-                    }
-
-                    if negate {
-                        regex_str.push_str("[^");
-                    } else {
-                        regex_str.push('[');
-                    }
-                    regex_str.push_str(&regex::escape(&bracket_content));
-                    regex_str.push(']');
-                }
-                '.' => {
-                    regex_str.push_str(".");
-                }
-                c => {
-                    regex_str.push_str(&regex::escape(&c.to_string()));
-                }
-            }
-            i += 1;
-        }
-
-        Regex::new(&regex_str).map_err(RunmunchError::Regex)
-    }
-
+    /// Whether the rule applies to `word`: the word must start (prefix) or end (suffix) with the
+    /// strip characters and match the condition there.
     pub fn can_apply(&self, word: &str, affix_type: &AffixType) -> bool {
-        if word.len() < self.strip.len() {
-            return false;
-        }
-
         match affix_type {
-            AffixType::Prefix => {
-                if !self.strip.is_empty() && !word.starts_with(&self.strip) {
-                    return false;
-                }
-                if let Some(ref condition) = self.condition {
-                    // For prefix rules, condition applies after the stripped part
-                    // Need to handle Unicode correctly
-                    let chars: Vec<char> = word.chars().collect();
-                    let strip_len = self.strip.chars().count();
-                    let condition_len = self.conditions_raw.chars().count();
-                    let check_start = strip_len;
-                    let check_end = std::cmp::min(chars.len(), check_start + condition_len);
-                    if check_start < chars.len() {
-                        let check_part: String = chars[check_start..check_end].iter().collect();
-                        condition.is_match(&check_part)
-                    } else {
-                        false
-                    }
-                } else {
-                    true
-                }
-            }
-            AffixType::Suffix => {
-                if !self.strip.is_empty() && !word.ends_with(&self.strip) {
-                    return false;
-                }
-                if let Some(ref condition) = self.condition {
-                    // For suffix rules, condition applies to the end of the original word
-                    // Need to handle Unicode correctly
-                    let chars: Vec<char> = word.chars().collect();
-                    let condition_len = self.conditions_raw.chars().count();
-                    let suffix_start = chars.len().saturating_sub(condition_len);
-                    let check_part: String = chars[suffix_start..].iter().collect();
-                    condition.is_match(&check_part)
-                } else {
-                    true
-                }
-            }
+            AffixType::Prefix => word.starts_with(&self.strip) && self.condition.matches_start(word),
+            AffixType::Suffix => word.ends_with(&self.strip) && self.condition.matches_end(word),
         }
     }
 
+    /// Whether applying the rule to `word` strips all of it, which hunspell only allows with
+    /// `FULLSTRIP`.
+    pub fn strips_whole_word(&self, word: &str) -> bool {
+        !self.strip.is_empty() && self.strip.chars().count() >= word.chars().count()
+    }
+
     pub fn apply(&self, word: &str, affix_type: &AffixType) -> String {
         match affix_type {
             AffixType::Prefix => {
@@ -183,10 +164,37 @@ pub struct AffixFile {
     pub suffixes: HashMap<String, Vec<AffixRule>>,
     pub flag_type: FlagType,
     pub fullstrip: bool,
+    /// `COMPLEXPREFIXES`: words take up to two prefixes and one suffix instead of one prefix
+    /// and two suffixes.
+    pub complex_prefixes: bool,
+    /// Flag marking affixes that must be combined with a prefix/suffix counterpart (`CIRCUMFIX`).
+    pub circumfix: Option<String>,
+    /// Flag marking roots and affixes that are not surface forms on their own
+    /// (`NEEDAFFIX`, or its older name `PSEUDOROOT`).
+    pub need_affix: Option<String>,
+    /// Flag marking forbidden words, left out of the output (`FORBIDDENWORD`).
+    pub forbidden_word: Option<String>,
+    /// Flag marking words never offered as suggestions (`NOSUGGEST`).
+    pub no_suggest: Option<String>,
+    /// Flag marking rare words that deserve a warning (`WARN`).
+    pub warn: Option<String>,
+    /// Flag marking substandard words, accepted but not suggested (`SUBSTANDARD`).
+    pub substandard: Option<String>,
+    pub compound: CompoundOptions,
+    pub suggest: SuggestOptions,
+    /// `ICONV`: conversions applied to input words.
+    pub iconv: ConversionTable,
+    /// `OCONV`: conversions applied to output words.
+    pub oconv: ConversionTable,
     pub flag_aliases: HashMap<String, Vec<String>>,
+    pub morph_aliases: HashMap<String, Vec<String>>,
+    /// Character set declared by the `SET` directive, if any.
+    pub encoding: Option<String>,
+    /// Problems found while parsing the file.
+    pub diagnostics: Vec<Diagnostic>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum FlagType {
     Single,
     Long,
@@ -194,6 +202,43 @@ pub enum FlagType {
     Utf8,
 }
 
+impl FlagType {
+    /// Splits a flag string into individual flags: one character each for `Single` and `Utf8`,
+    /// two characters each for `Long`, and comma-separated numbers (1-65535) for `Numeric`.
+    pub fn split(&self, flags_str: &str) -> Result<Vec<String>> {
+        if flags_str.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        match self {
+            FlagType::Single | FlagType::Utf8 => Ok(flags_str.chars().map(|c| c.to_string()).collect()),
+            FlagType::Long => {
+                let chars: Vec<char> = flags_str.chars().collect();
+                if !chars.len().is_multiple_of(2) {
+                    return Err(RunmunchError::InvalidFlag(format!("Odd number of characters in long flags: {}", flags_str)));
+                }
+                Ok(chars.chunks(2).map(|chunk| chunk.iter().collect()).collect())
+            }
+            FlagType::Numeric => {
+                flags_str.split(',')
+                    .map(|flag| match flag.parse::<u16>() {
+                        Ok(number) if number > 0 => Ok(flag.to_string()),
+                        _ => Err(RunmunchError::InvalidFlag(format!("Invalid numeric flag {:?} in: {}", flag, flags_str))),
+                    })
+                    .collect()
+            }
+        }
+    }
+
+    /// Joins flags into a flag string, the inverse of `split`.
+    pub fn join(&self, flags: &[String]) -> String {
+        match self {
+            FlagType::Numeric => flags.join(","),
+            FlagType::Single | FlagType::Long | FlagType::Utf8 => flags.concat(),
+        }
+    }
+}
+
 impl AffixFile {
     pub fn new() -> Self {
         AffixFile {
@@ -201,18 +246,51 @@ impl AffixFile {
             suffixes: HashMap::new(),
             flag_type: FlagType::Single,
             fullstrip: false,
+            complex_prefixes: false,
+            circumfix: None,
+            need_affix: None,
+            forbidden_word: None,
+            no_suggest: None,
+            warn: None,
+            substandard: None,
+            compound: CompoundOptions::new(),
+            suggest: SuggestOptions::new(),
+            iconv: ConversionTable::new(),
+            oconv: ConversionTable::new(),
             flag_aliases: HashMap::new(),
+            morph_aliases: HashMap::new(),
+            encoding: None,
+            diagnostics: Vec::new(),
         }
     }
 
+    /// Loads an affix file, decoding it according to its `SET` directive.
     pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
-        let content = fs::read_to_string(path)?;
-        Self::parse(&content)
+        Self::load_with_mode(path, ParseMode::Strict)
+    }
+
+    /// Loads an affix file, collecting warnings (and, in lenient mode, errors) in `diagnostics`.
+    pub fn load_with_mode<P: AsRef<Path>>(path: P, mode: ParseMode) -> Result<Self> {
+        let bytes = fs::read(&path)?;
+        let charset = encoding::detect_charset(&bytes);
+        let content = encoding::decode(&bytes, charset.as_deref())?;
+        Self::parse_source(&content, Some(path.as_ref().display().to_string()), mode)
     }
 
     pub fn parse(content: &str) -> Result<Self> {
+        Self::parse_with_mode(content, ParseMode::Strict)
+    }
+
+    /// Parses an affix file, collecting warnings (and, in lenient mode, errors) in `diagnostics`.
+    pub fn parse_with_mode(content: &str, mode: ParseMode) -> Result<Self> {
+        Self::parse_source(content, None, mode)
+    }
+
+    fn parse_source(content: &str, file: Option<String>, mode: ParseMode) -> Result<Self> {
         let mut affix_file = AffixFile::new();
+        let mut diagnostics = Diagnostics::new(file, mode, Source::Affix);
         let lines: Vec<&str> = content.lines().collect();
+        let mut headers = TableHeaders::default();
         let mut i = 0;
 
         while i < lines.len() {
@@ -224,126 +302,269 @@ impl AffixFile {
             }
 
             let parts: Vec<&str> = line.split_whitespace().collect();
-            
-            match parts.get(0) {
-                Some(&"FLAG") => {
-                    if let Some(flag_type) = parts.get(1) {
-                        affix_file.flag_type = match *flag_type {
-                            "long" => FlagType::Long,
-                            "num" => FlagType::Numeric,
-                            "UTF-8" => FlagType::Utf8,
-                            _ => FlagType::Single,
-                        };
-                    }
-                }
-                Some(&"FULLSTRIP") => {
-                    affix_file.fullstrip = true;
-                }
-                Some(&"AF") => {
-                    if parts.len() >= 2 {
-                        // Look for the alias index in the comment (# number)
-                        let alias_index = if let Some(comment_pos) = line.find('#') {
-                            let comment_part = &line[comment_pos + 1..].trim();
-                            comment_part.parse::<u32>().unwrap_or((affix_file.flag_aliases.len() + 1) as u32).to_string()
-                        } else {
-                            (affix_file.flag_aliases.len() + 1).to_string()
-                        };
-                        
-                        let flags_str = parts[1].to_string(); // Take just the first part (before #)
-                        
-                        // For long flags, split by pairs; for single flags, split by character
-                        let flags = match affix_file.flag_type {
-                            FlagType::Long => {
-                                flags_str.chars()
-                                    .collect::<Vec<_>>()
-                                    .chunks(2)
-                                    .map(|chunk| chunk.iter().collect::<String>())
-                                    .collect()
-                            },
-                            _ => {
-                                flags_str.chars().map(|c| c.to_string()).collect()
-                            }
-                        };
-                        
-                        affix_file.flag_aliases.insert(alias_index, flags);
-                    }
-                }
-                Some(&"PFX") | Some(&"SFX") => {
-                    if parts.len() >= 4 && (parts[2] == "Y" || parts[2] == "N") {
-                        // This is a header line
-                        let affix_type = if parts[0] == "PFX" { AffixType::Prefix } else { AffixType::Suffix };
-                        let advance = affix_file.parse_affix_block(&lines, i, affix_type)?;
-                        i += advance;
-                        continue;
-                    }
-                    // Otherwise, it's a rule line that we'll skip
-                }
-                _ => {}
+
+            if (parts[0] == "PFX" || parts[0] == "SFX") && parts.len() >= 4 && (parts[2] == "Y" || parts[2] == "N") {
+                // This is a header line; its rule lines are consumed by parse_affix_block
+                let affix_type = if parts[0] == "PFX" { AffixType::Prefix } else { AffixType::Suffix };
+                i += affix_file.parse_affix_block(&lines, i, affix_type, &mut diagnostics)?;
+                continue;
             }
-            
+
+            if !KNOWN_DIRECTIVES.contains(&parts[0]) {
+                diagnostics.warn(i + 1, field_column(lines[i], 0), DiagnosticCode::UnknownDirective, format!("Unknown directive {}", parts[0]));
+            } else if let Err(error) = affix_file.parse_directive(&parts, &mut headers) {
+                diagnostics.error_from(i + 1, field_column(lines[i], 1), error)?;
+            }
+
             i += 1;
         }
 
+        affix_file.diagnostics = diagnostics.list;
         Ok(affix_file)
     }
 
-    fn parse_affix_block(&mut self, lines: &[&str], start: usize, affix_type: AffixType) -> Result<usize> {
-        if start >= lines.len() {
-            return Ok(0);
+    fn parse_directive(&mut self, parts: &[&str], headers: &mut TableHeaders) -> Result<()> {
+        match parts.first() {
+            Some(&"FLAG") => {
+                if let Some(flag_type) = parts.get(1) {
+                    self.flag_type = match *flag_type {
+                        "long" => FlagType::Long,
+                        "num" => FlagType::Numeric,
+                        "UTF-8" => FlagType::Utf8,
+                        _ => FlagType::Single,
+                    };
+                }
+            }
+            Some(&"SET") => {
+                self.encoding = parts.get(1).map(|charset| charset.to_string());
+            }
+            Some(&"COMPLEXPREFIXES") => {
+                self.complex_prefixes = true;
+            }
+            Some(&"CIRCUMFIX") => {
+                self.circumfix = parts.get(1).map(|flag| flag.to_string());
+            }
+            Some(&"NEEDAFFIX") | Some(&"PSEUDOROOT") => {
+                self.need_affix = parts.get(1).map(|flag| flag.to_string());
+            }
+            Some(&"FORBIDDENWORD") => {
+                self.forbidden_word = parts.get(1).map(|flag| flag.to_string());
+            }
+            Some(&"NOSUGGEST") => {
+                self.no_suggest = parts.get(1).map(|flag| flag.to_string());
+            }
+            Some(&"WARN") => {
+                self.warn = parts.get(1).map(|flag| flag.to_string());
+            }
+            Some(&"SUBSTANDARD") => {
+                self.substandard = parts.get(1).map(|flag| flag.to_string());
+            }
+            Some(&"COMPOUNDFLAG") => {
+                self.compound.flag = parts.get(1).map(|flag| flag.to_string());
+            }
+            Some(&"COMPOUNDBEGIN") => {
+                self.compound.begin = parts.get(1).map(|flag| flag.to_string());
+            }
+            Some(&"COMPOUNDMIDDLE") => {
+                self.compound.middle = parts.get(1).map(|flag| flag.to_string());
+            }
+            Some(&"COMPOUNDEND") => {
+                self.compound.end = parts.get(1).map(|flag| flag.to_string());
+            }
+            Some(&"ONLYINCOMPOUND") => {
+                self.compound.only_in_compound = parts.get(1).map(|flag| flag.to_string());
+            }
+            Some(&"COMPOUNDPERMITFLAG") => {
+                self.compound.permit = parts.get(1).map(|flag| flag.to_string());
+            }
+            Some(&"COMPOUNDFORBIDFLAG") => {
+                self.compound.forbid = parts.get(1).map(|flag| flag.to_string());
+            }
+            Some(&"COMPOUNDMIN") if parts.len() >= 2 => {
+                let min_length: usize = parts[1].parse()
+                    .map_err(|_| RunmunchError::InvalidAffix(format!("Invalid COMPOUNDMIN: {}", parts[1])))?;
+                // Hunspell treats values below 1 as 1
+                self.compound.min_length = min_length.max(1);
+            }
+            Some(&"COMPOUNDWORDMAX") if parts.len() >= 2 => {
+                let word_max = parts[1].parse()
+                    .map_err(|_| RunmunchError::InvalidAffix(format!("Invalid COMPOUNDWORDMAX: {}", parts[1])))?;
+                self.compound.word_max = Some(word_max);
+            }
+            Some(&"FULLSTRIP") => {
+                self.fullstrip = true;
+            }
+            Some(&"AF") if parts.len() >= 2 => {
+                // The first AF line declares the table size; aliases are numbered from 1 in
+                // the order they follow it
+                if !headers.flag_alias {
+                    Self::parse_table_size(parts)?;
+                    headers.flag_alias = true;
+                } else {
+                    let alias_index = (self.flag_aliases.len() + 1).to_string();
+                    let flags = self.flag_type.split(parts[1])?;
+                    self.flag_aliases.insert(alias_index, flags);
+                }
+            }
+            Some(&"AM") if parts.len() >= 2 => {
+                if !headers.morph_alias {
+                    Self::parse_table_size(parts)?;
+                    headers.morph_alias = true;
+                } else {
+                    let alias_index = (self.morph_aliases.len() + 1).to_string();
+                    let fields = parts[1..].iter().map(|field| field.to_string()).collect();
+                    self.morph_aliases.insert(alias_index, fields);
+                }
+            }
+            // The table header only carries the entry count
+            Some(&"ICONV") if parts.len() >= 3 => {
+                self.iconv.add(parts[1], parts[2]);
+            }
+            Some(&"OCONV") if parts.len() >= 3 => {
+                self.oconv.add(parts[1], parts[2]);
+            }
+            Some(&"TRY") if parts.len() >= 2 => {
+                self.suggest.try_chars = parts[1].to_string();
+            }
+            Some(&"KEY") if parts.len() >= 2 => {
+                self.suggest.keyboard = Some(parts[1].to_string());
+            }
+            Some(&"MAXDIFF") if parts.len() >= 2 => {
+                let max_diff: usize = parts[1].parse()
+                    .map_err(|_| RunmunchError::InvalidAffix(format!("Invalid MAXDIFF: {}", parts[1])))?;
+                self.suggest.max_diff = Some(max_diff.min(10));
+            }
+            Some(&"NOSPLITSUGS") => {
+                self.suggest.no_split = true;
+            }
+            // Like ICONV, the REP header only carries the entry count
+            Some(&"REP") if parts.len() >= 3 => {
+                self.suggest.replacements.push((parts[1].to_string(), parts[2].to_string()));
+            }
+            Some(&"MAP") if parts.len() >= 2 => {
+                if !headers.map && parts[1].chars().all(|c| c.is_ascii_digit()) {
+                    headers.map = true;
+                } else {
+                    self.suggest.map.push(SuggestOptions::parse_map(parts[1]));
+                }
+            }
+            Some(&"COMPOUNDRULE") if parts.len() >= 2 => {
+                // The first COMPOUNDRULE line declares the number of rules that follow
+                if !headers.compound_rule && parts[1].chars().all(|c| c.is_ascii_digit()) {
+                    headers.compound_rule = true;
+                } else {
+                    let rule = CompoundRule::parse(parts[1], &self.flag_type)?;
+                    self.compound.rules.push(rule);
+                }
+            }
+            _ => {}
         }
+        Ok(())
+    }
 
-        let header_parts: Vec<&str> = lines[start].split_whitespace().collect();
-        if header_parts.len() < 3 {
-            return Err(RunmunchError::InvalidAffix(format!("Invalid affix header: {:?}", header_parts)));
-        }
+    fn parse_table_size(parts: &[&str]) -> Result<usize> {
+        parts[1].parse()
+            .map_err(|_| RunmunchError::InvalidAffix(format!("Invalid {} table size: {}", parts[0], parts[1])))
+    }
 
+    /// Parses a `PFX`/`SFX` block starting at the header line `start`, returning the number of
+    /// lines it spans.
+    fn parse_affix_block(&mut self, lines: &[&str], start: usize, affix_type: AffixType, diagnostics: &mut Diagnostics) -> Result<usize> {
+        let header_line = lines[start];
+        let header_parts: Vec<&str> = header_line.split_whitespace().collect();
         let flag = header_parts[1].to_string();
         let cross_product = header_parts[2] == "Y";
-        let count: usize = if header_parts.len() >= 4 {
-            header_parts[3].parse()
-                .map_err(|_| RunmunchError::InvalidAffix(format!("Invalid rule count: {}", header_parts[3])))?
-        } else {
-            return Err(RunmunchError::InvalidAffix(format!("Missing rule count in line: {}", lines[start])));
+        let count = match header_parts[3].parse::<usize>() {
+            Ok(count) => Some(count),
+            Err(_) => {
+                let message = format!("Invalid rule count: {}", header_parts[3]);
+                diagnostics.error(start + 1, field_column(header_line, 3), DiagnosticCode::InvalidValue, message)?;
+                None
+            }
         };
 
-        let mut rules = Vec::new();
-        let mut processed = 1;
-
-        for i in 1..=count {
-            if start + i >= lines.len() {
+        // Rule lines repeat the type and flag of the header. The block holds the declared number
+        // of them when the line after those starts a new block; otherwise the count is wrong, and
+        // the block ends before the first line shaped like a header.
+        let is_header = |parts: &[&str]| {
+            parts.len() == 4 && (parts[2] == "Y" || parts[2] == "N") && parts[3].chars().all(|c| c.is_ascii_digit())
+        };
+        let mut candidates = Vec::new();
+        for (index, line) in lines.iter().enumerate().skip(start + 1) {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            if parts.len() < 2 || parts[0] != header_parts[0] || parts[1] != flag {
                 break;
             }
+            candidates.push((index, parts));
+        }
+        let trusted_count = count.filter(|&count| {
+            count <= candidates.len() && candidates.get(count).is_none_or(|(_, parts)| is_header(parts))
+        });
+        let block_size = trusted_count
+            .unwrap_or_else(|| candidates.iter().position(|(_, parts)| is_header(parts)).unwrap_or(candidates.len()));
 
-            let rule_line = lines[start + i].trim();
-            if rule_line.is_empty() || rule_line.starts_with('#') {
-                processed = i;
+        let mut rules = Vec::new();
+        let mut next = start + 1;
+
+        for (index, rule_parts) in candidates.into_iter().take(block_size) {
+            let raw_line = lines[index];
+            let rule_line = raw_line.trim();
+            next = index + 1;
+
+            if rule_parts.len() < 4 {
+                let message = format!("Incomplete rule: {}", rule_line);
+                diagnostics.error(next, field_column(raw_line, rule_parts.len()), DiagnosticCode::InvalidValue, message)?;
                 continue;
             }
 
-            let rule_parts: Vec<&str> = rule_line.split_whitespace().collect();
-            if rule_parts.len() >= 4 && rule_parts[0] == header_parts[0] && rule_parts[1] == flag {
-                let strip = if rule_parts[2] == "0" { String::new() } else { rule_parts[2].to_string() };
-                let affix_str = if rule_parts[3] == "0" { String::new() } else {
-                    rule_parts[3].split('/').next().unwrap_or("").to_string()
-                };
-                let condition = rule_parts.get(4).unwrap_or(&".").to_string();
-
-                let rule = AffixRule::new(flag.clone(), cross_product, strip, affix_str, condition)?;
-                rules.push(rule);
+            let strip = if rule_parts[2] == "0" { String::new() } else { rule_parts[2].to_string() };
+            let (affix_part, continuation_part) = match rule_parts[3].split_once('/') {
+                Some((affix_part, continuation_part)) => (affix_part, continuation_part),
+                None => (rule_parts[3], ""),
+            };
+            let affix_str = if affix_part == "0" { String::new() } else { affix_part.to_string() };
+            let continuation = match self.split_flags(continuation_part) {
+                Ok(continuation) => continuation,
+                Err(error) => {
+                    diagnostics.error_from(next, field_column(raw_line, 3), error)?;
+                    continue;
+                }
+            };
+            let condition = rule_parts.get(4).unwrap_or(&".").to_string();
+            let morph = self.expand_morph(rule_parts.get(5..).unwrap_or_default());
+
+            match AffixRule::new(flag.clone(), cross_product, strip, affix_str, condition, continuation, morph) {
+                Ok(rule) => rules.push(rule),
+                Err(error) => {
+                    let message = match error {
+                        RunmunchError::InvalidAffix(message) => message,
+                        error => error.to_string(),
+                    };
+                    diagnostics.error(next, field_column(raw_line, 4), DiagnosticCode::BadCondition, message)?;
+                }
             }
-            processed = i;
         }
 
-        match affix_type {
-            AffixType::Prefix => {
-                self.prefixes.insert(flag, rules);
-            }
-            AffixType::Suffix => {
-                self.suffixes.insert(flag, rules);
-            }
+        if count.is_some_and(|count| count != block_size) {
+            let message = format!("{} {} declares {} rules but {} follow", header_parts[0], flag, header_parts[3], block_size);
+            diagnostics.warn(start + 1, field_column(header_line, 3), DiagnosticCode::RuleCountMismatch, message);
+        }
+
+        let blocks = match affix_type {
+            AffixType::Prefix => &mut self.prefixes,
+            AffixType::Suffix => &mut self.suffixes,
+        };
+        if blocks.contains_key(&flag) {
+            let message = format!("{} flag {} is defined by more than one block; their rules are merged", header_parts[0], flag);
+            diagnostics.warn(start + 1, field_column(header_line, 1), DiagnosticCode::DuplicateFlagBlock, message);
         }
+        blocks.entry(flag).or_default().extend(rules);
 
-        Ok(processed)
+        Ok(next - start)
     }
 
     pub fn get_prefix_rules(&self, flag: &str) -> Option<&Vec<AffixRule>> {
@@ -354,21 +575,260 @@ impl AffixFile {
         self.suffixes.get(flag)
     }
 
+    /// Maximum number of affixes of the given type in one word form: one prefix and two
+    /// suffixes by default, two prefixes and one suffix with `COMPLEXPREFIXES`.
+    pub fn max_affixes(&self, affix_type: &AffixType) -> usize {
+        match (affix_type, self.complex_prefixes) {
+            (AffixType::Prefix, false) | (AffixType::Suffix, true) => 1,
+            (AffixType::Prefix, true) | (AffixType::Suffix, false) => 2,
+        }
+    }
+
+    /// Whether `rule` applies to `word`. Rules may strip the whole word only with `FULLSTRIP`, and
+    /// never when that would leave an empty word.
+    pub fn rule_applies(&self, rule: &AffixRule, word: &str, affix_type: &AffixType) -> bool {
+        if !rule.can_apply(word, affix_type) {
+            return false;
+        }
+        !rule.strips_whole_word(word) || (self.fullstrip && !rule.affix.is_empty())
+    }
+
+    /// Undoes `rule` on `word`, returning the root it would have been applied to, unless the rule
+    /// would have stripped that root entirely without `FULLSTRIP`.
+    pub fn reverse_rule(&self, rule: &AffixRule, word: &str, affix_type: &AffixType) -> Option<String> {
+        let root = rule.reverse_apply(word, affix_type)?;
+        if root.is_empty() || (rule.strips_whole_word(&root) && !self.fullstrip) {
+            return None;
+        }
+        Some(root)
+    }
+
+    /// Whether `flags` (of a root or an affix continuation class) carry the `CIRCUMFIX` flag.
+    pub fn is_circumfix(&self, flags: &[String]) -> bool {
+        self.circumfix.as_ref().is_some_and(|flag| flags.contains(flag))
+    }
+
+    /// Whether `flags` (of a root or an affix continuation class) carry the `NEEDAFFIX` flag.
+    pub fn needs_affix(&self, flags: &[String]) -> bool {
+        self.need_affix.as_ref().is_some_and(|flag| flags.contains(flag))
+    }
+
+    /// Entry status signalled by `flags` (of a root or an affix continuation class).
+    pub fn word_status(&self, flags: &[String]) -> WordStatus {
+        let has = |directive: &Option<String>| directive.as_ref().is_some_and(|flag| flags.contains(flag));
+        WordStatus {
+            forbidden: has(&self.forbidden_word),
+            no_suggest: has(&self.no_suggest),
+            warn: has(&self.warn),
+            substandard: has(&self.substandard),
+            only_in_compound: has(&self.compound.only_in_compound),
+        }
+    }
+
+    /// Splits a flag string (as found after a `/`) into individual flags according to `flag_type`,
+    /// or resolves it through the `AF` table when aliases are in use.
+    pub fn split_flags(&self, flags_str: &str) -> Result<Vec<String>> {
+        if self.flag_aliases.is_empty() || flags_str.is_empty() {
+            return self.flag_type.split(flags_str);
+        }
+        self.flag_aliases.get(flags_str).cloned().ok_or_else(|| {
+            RunmunchError::InvalidFlag(format!("AF alias {} out of range (1-{})", flags_str, self.flag_aliases.len()))
+        })
+    }
+
+    /// The `AF` alias number standing for `flags`, in any order; the lowest one when several do.
+    pub fn find_flag_alias(&self, flags: &[String]) -> Option<String> {
+        let mut wanted = flags.to_vec();
+        wanted.sort();
+        self.flag_aliases.iter()
+            .filter(|(_, alias_flags)| {
+                let mut alias_flags = alias_flags.to_vec();
+                alias_flags.sort();
+                alias_flags == wanted
+            })
+            .map(|(alias, _)| alias.clone())
+            .min_by_key(|alias| alias.parse::<usize>().unwrap_or(usize::MAX))
+    }
+
+    /// The `AF` alias number standing for `flags`, appending a new alias to the table when none
+    /// does. Save the affix file afterwards to keep the new alias.
+    pub fn add_flag_alias(&mut self, flags: &[String]) -> String {
+        if let Some(alias) = self.find_flag_alias(flags) {
+            return alias;
+        }
+        let alias = (self.flag_aliases.len() + 1).to_string();
+        self.flag_aliases.insert(alias.clone(), flags.to_vec());
+        alias
+    }
+
     pub fn resolve_flag_alias(&self, alias: &str) -> Vec<String> {
         self.flag_aliases.get(alias).cloned().unwrap_or_else(|| vec![alias.to_string()])
     }
 
-    pub fn expand_flags(&self, flags: &[String]) -> Vec<String> {
-        let mut expanded = Vec::new();
-        for flag in flags {
-            if flag.chars().all(|c| c.is_ascii_digit()) {
-                // This is a numeric alias
-                expanded.extend(self.resolve_flag_alias(flag));
-            } else {
-                expanded.push(flag.clone());
+    /// Resolves morphological fields, replacing a lone `AM` alias number by the fields it stands for.
+    pub fn expand_morph<S: AsRef<str>>(&self, fields: &[S]) -> Vec<String> {
+        if let [alias] = fields {
+            if let Some(resolved) = self.morph_aliases.get(alias.as_ref()) {
+                return resolved.clone();
+            }
+        }
+        fields.iter().map(|field| field.as_ref().to_string()).collect()
+    }
+
+    /// Writes the affix file in hunspell's `.aff` format, in the character set of its `SET`
+    /// directive.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let content = self.serialize();
+        let bytes = match &self.encoding {
+            Some(charset) => encoding::encode(&content, charset)?,
+            None => content.into_bytes(),
+        };
+        fs::write(path, bytes)?;
+        Ok(())
+    }
+
+    /// Serializes the affix file in hunspell's `.aff` format. Blocks are written sorted by flag;
+    /// continuation classes and morphological fields that the `AF`/`AM` tables in use lack are
+    /// added to them.
+    pub fn serialize(&self) -> String {
+        let mut flag_aliases = Self::alias_table(&self.flag_aliases);
+        let mut morph_aliases = Self::alias_table(&self.morph_aliases);
+
+        // Rule lines come first, as they may extend the alias tables
+        let mut block_lines = Vec::new();
+        for (kind, blocks) in [("PFX", &self.prefixes), ("SFX", &self.suffixes)] {
+            let mut flags: Vec<&String> = blocks.keys().collect();
+            flags.sort();
+            for flag in flags {
+                let rules = &blocks[flag];
+                let cross_product = if rules.first().is_none_or(|rule| rule.cross_product) { "Y" } else { "N" };
+                block_lines.push(String::new());
+                block_lines.push(format!("{} {} {} {}", kind, flag, cross_product, rules.len()));
+
+                for rule in rules {
+                    let or_zero = |text: &str| if text.is_empty() { "0".to_string() } else { text.to_string() };
+                    let mut line = format!("{} {} {} {}", kind, flag, or_zero(&rule.strip), or_zero(&rule.affix));
+                    if !rule.continuation.is_empty() {
+                        line.push('/');
+                        if self.flag_aliases.is_empty() {
+                            line.push_str(&self.flag_type.join(&rule.continuation));
+                        } else {
+                            line.push_str(&Self::alias_for(&mut flag_aliases, &rule.continuation));
+                        }
+                    }
+                    line.push(' ');
+                    line.push_str(if rule.conditions_raw.is_empty() { "." } else { &rule.conditions_raw });
+                    if !rule.morph.is_empty() {
+                        line.push(' ');
+                        if self.morph_aliases.is_empty() {
+                            line.push_str(&rule.morph.join(" "));
+                        } else {
+                            line.push_str(&Self::alias_for(&mut morph_aliases, &rule.morph));
+                        }
+                    }
+                    block_lines.push(line);
+                }
+            }
+        }
+
+        let mut lines = Vec::new();
+        if let Some(charset) = &self.encoding {
+            lines.push(format!("SET {}", charset));
+        }
+        match self.flag_type {
+            FlagType::Single => {}
+            FlagType::Long => lines.push("FLAG long".to_string()),
+            FlagType::Numeric => lines.push("FLAG num".to_string()),
+            FlagType::Utf8 => lines.push("FLAG UTF-8".to_string()),
+        }
+        if self.complex_prefixes {
+            lines.push("COMPLEXPREFIXES".to_string());
+        }
+        if self.fullstrip {
+            lines.push("FULLSTRIP".to_string());
+        }
+
+        let compound = &self.compound;
+        let flag_directives = [
+            ("CIRCUMFIX", &self.circumfix),
+            ("NEEDAFFIX", &self.need_affix),
+            ("FORBIDDENWORD", &self.forbidden_word),
+            ("NOSUGGEST", &self.no_suggest),
+            ("WARN", &self.warn),
+            ("SUBSTANDARD", &self.substandard),
+            ("COMPOUNDFLAG", &compound.flag),
+            ("COMPOUNDBEGIN", &compound.begin),
+            ("COMPOUNDMIDDLE", &compound.middle),
+            ("COMPOUNDEND", &compound.end),
+            ("ONLYINCOMPOUND", &compound.only_in_compound),
+            ("COMPOUNDPERMITFLAG", &compound.permit),
+            ("COMPOUNDFORBIDFLAG", &compound.forbid),
+        ];
+        for (directive, flag) in flag_directives {
+            if let Some(flag) = flag {
+                lines.push(format!("{} {}", directive, flag));
+            }
+        }
+        if compound.min_length != DEFAULT_COMPOUND_MIN {
+            lines.push(format!("COMPOUNDMIN {}", compound.min_length));
+        }
+        if let Some(word_max) = compound.word_max {
+            lines.push(format!("COMPOUNDWORDMAX {}", word_max));
+        }
+
+        let suggest = &self.suggest;
+        if !suggest.try_chars.is_empty() {
+            lines.push(format!("TRY {}", suggest.try_chars));
+        }
+        if let Some(keyboard) = &suggest.keyboard {
+            lines.push(format!("KEY {}", keyboard));
+        }
+        if let Some(max_diff) = suggest.max_diff {
+            lines.push(format!("MAXDIFF {}", max_diff));
+        }
+        if suggest.no_split {
+            lines.push("NOSPLITSUGS".to_string());
+        }
+
+        let mut table = |directive: &str, rows: Vec<String>| {
+            if !rows.is_empty() {
+                lines.push(format!("{} {}", directive, rows.len()));
+                lines.extend(rows.into_iter().map(|row| format!("{} {}", directive, row)));
             }
+        };
+        table("COMPOUNDRULE", compound.rules.iter().map(|rule| rule.pattern.clone()).collect());
+        table("AF", flag_aliases.iter().map(|flags| self.flag_type.join(flags)).collect());
+        table("AM", morph_aliases.iter().map(|fields| fields.join(" ")).collect());
+        table("REP", suggest.replacements.iter().map(|(from, to)| format!("{} {}", from, to)).collect());
+        table("MAP", suggest.map.iter().map(|elements| SuggestOptions::format_map(elements)).collect());
+        for (directive, conversions) in [("ICONV", &self.iconv), ("OCONV", &self.oconv)] {
+            table(directive, conversions.entries.iter().map(|(from, to)| format!("{} {}", from, to)).collect());
         }
-        expanded
+
+        lines.extend(block_lines);
+        lines.push(String::new());
+        lines.join("\n")
+    }
+
+    /// An alias table as a list, alias `n` at index `n - 1`.
+    fn alias_table(aliases: &HashMap<String, Vec<String>>) -> Vec<Vec<String>> {
+        let mut numbered: Vec<(usize, &Vec<String>)> = aliases.iter()
+            .filter_map(|(alias, values)| alias.parse().ok().map(|index| (index, values)))
+            .collect();
+        numbered.sort();
+        numbered.into_iter().map(|(_, values)| values.clone()).collect()
+    }
+
+    /// The alias number of `values`, appending them to the table when missing.
+    fn alias_for(table: &mut Vec<Vec<String>>, values: &[String]) -> String {
+        let index = match table.iter().position(|existing| existing == values) {
+            Some(index) => index,
+            None => {
+                table.push(values.to_vec());
+                table.len() - 1
+            }
+        };
+        (index + 1).to_string()
     }
 }
 
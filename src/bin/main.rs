@@ -1,8 +1,25 @@
-use clap::{Arg, Command};
-use runmunch::{Runmunch, WordExpander};
-use std::io::{self, BufRead, BufReader};
+use clap::{Arg, ArgMatches, Command};
+use runmunch::{encoding, AffixFile, CompoundLimits, DerivedForm, Diagnostic, DictionaryEntry, ParseMode, Runmunch, WordExpander};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read, Write};
 use std::process;
 
+fn affix_arg() -> Arg {
+    Arg::new("affix")
+        .help("Affix file (.aff)")
+        .required(true)
+        .value_name("AFFIX")
+        .index(1)
+}
+
+fn dictionary_arg() -> Arg {
+    Arg::new("dictionary")
+        .help("Dictionary file (.dic)")
+        .required(true)
+        .value_name("DICTIONARY")
+        .index(2)
+}
+
 fn main() {
     let matches = Command::new("runmunch")
         .version(env!("CARGO_PKG_VERSION"))
@@ -36,65 +53,431 @@ fn main() {
                 .help("Find base word from inflected forms and expand using affix rules (requires dictionary)")
                 .action(clap::ArgAction::SetTrue),
         )
+        .arg(
+            Arg::new("recode-output")
+                .short('r')
+                .long("recode-output")
+                .help("Encode output in the character set declared by the affix file's SET directive instead of UTF-8")
+                .global(true)
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("compounds")
+                .long("compounds")
+                .help("Also generate compounds of up to PARTS parts when unmunching")
+                .value_name("PARTS")
+                .value_parser(clap::value_parser!(usize)),
+        )
+        .arg(
+            Arg::new("compound-max-length")
+                .long("compound-max-length")
+                .help("Maximum length of generated compounds, in characters")
+                .value_name("LENGTH")
+                .default_value("30")
+                .value_parser(clap::value_parser!(usize)),
+        )
+        .arg(
+            Arg::new("compound-heads")
+                .long("compound-heads")
+                .help("Only generate compounds whose last part derives from one of the dictionary words listed in FILE, one per line")
+                .value_name("FILE")
+                .requires("compounds"),
+        )
+        .arg(
+            Arg::new("no-iconv")
+                .long("no-iconv")
+                .help("Do not apply the affix file's ICONV table to input words")
+                .global(true)
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("no-oconv")
+                .long("no-oconv")
+                .help("Do not apply the affix file's OCONV table to output words")
+                .global(true)
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("provenance")
+                .long("provenance")
+                .help("Print the dictionary entry, the affix rules (PFX/SFX:flag:index) and whether a cross product produced each word, tab-separated")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("format")
+                .long("format")
+                .help("Output format: one word per line (plain), a JSON object with word, base and flags per word (jsonl), word and lemma columns (tsv), or each input word or dictionary entry with its forms on one line (grouped)")
+                .value_name("FORMAT")
+                .value_parser(["plain", "jsonl", "tsv", "grouped"])
+                .default_value("plain"),
+        )
+        .arg(
+            Arg::new("lenient")
+                .long("lenient")
+                .help("Report malformed lines of the affix and dictionary files and skip them instead of failing")
+                .global(true)
+                .action(clap::ArgAction::SetTrue),
+        )
+        .subcommand_negates_reqs(true)
+        .args_conflicts_with_subcommands(true)
+        .subcommand(
+            Command::new("munch")
+                .about("Compress a word list into dictionary entries with the affix file's flags, printed as a .dic file")
+                .arg(affix_arg())
+                .arg(
+                    Arg::new("words")
+                        .help("Word list, one word per line (default: stdin)")
+                        .value_name("WORDS")
+                        .index(2),
+                )
+                .arg(
+                    Arg::new("affix-output")
+                        .long("affix-output")
+                        .help("Write the affix file to FILE, with the AF aliases the new entries need (required when it uses AF and aliases are missing)")
+                        .value_name("FILE"),
+                ),
+        )
+        .subcommand(
+            Command::new("analyze")
+                .about("Print the morphological analyses of words from stdin, one 'WORD st:... po:... is:...' line each, with a blank line after each word")
+                .arg(affix_arg())
+                .arg(dictionary_arg()),
+        )
+        .subcommand(
+            Command::new("stem")
+                .about("Print 'WORD STEM' for each stem of the words from stdin, or the word alone when it is unknown")
+                .arg(affix_arg())
+                .arg(dictionary_arg()),
+        )
+        .subcommand(
+            Command::new("suggest")
+                .about("Spellcheck words from stdin: print '*' for a correct word, '& WORD: SUGGESTIONS' or '# WORD' otherwise")
+                .arg(affix_arg())
+                .arg(dictionary_arg()),
+        )
         .get_matches();
 
+    if let Some((mode, sub_matches)) = matches.subcommand() {
+        let options = Options::from_matches(sub_matches);
+        let affix_file = sub_matches.get_one::<String>("affix").unwrap();
+        let result = match mode {
+            "munch" => run_munch_mode(
+                affix_file,
+                sub_matches.get_one::<String>("words"),
+                sub_matches.get_one::<String>("affix-output"),
+                &options,
+            ),
+            "analyze" => run_analyze_mode(affix_file, sub_matches.get_one::<String>("dictionary").unwrap(), &options),
+            "stem" => run_stem_mode(affix_file, sub_matches.get_one::<String>("dictionary").unwrap(), &options),
+            "suggest" => run_suggest_mode(affix_file, sub_matches.get_one::<String>("dictionary").unwrap(), &options),
+            _ => unreachable!("unknown subcommand {}", mode),
+        };
+        if let Err(e) = result {
+            eprintln!("Error: {}", e);
+            process::exit(1);
+        }
+        return;
+    }
+
     let affix_file = matches.get_one::<String>("affix").unwrap();
+    let options = Options::from_matches(&matches);
 
     if matches.get_flag("find-base") {
         let dictionary_file = matches.get_one::<String>("dictionary")
             .ok_or("Dictionary file is required for --find-base mode").unwrap();
-        if let Err(e) = run_find_base_mode(affix_file, dictionary_file) {
+        if let Err(e) = run_find_base_mode(affix_file, dictionary_file, &options) {
             eprintln!("Error: {}", e);
             process::exit(1);
         }
     } else if matches.get_flag("expand") {
         let dictionary_file = matches.get_one::<String>("dictionary");
-        if let Err(e) = run_expand_mode(affix_file, dictionary_file) {
+        if let Err(e) = run_expand_mode(affix_file, dictionary_file, &options) {
             eprintln!("Error: {}", e);
             process::exit(1);
         }
     } else {
         let dictionary_file = matches.get_one::<String>("dictionary").unwrap();
-        if let Err(e) = run_unmunch_mode(affix_file, dictionary_file) {
+        if let Err(e) = run_unmunch_mode(affix_file, dictionary_file, &options) {
             eprintln!("Error: {}", e);
             process::exit(1);
         }
     }
 }
 
-fn run_expand_mode(affix_file: &str, dictionary_file: Option<&String>) -> Result<(), Box<dyn std::error::Error>> {
-    let affix = runmunch::AffixFile::load(affix_file)?;
+/// Settings shared by all modes.
+struct Options {
+    recode: bool,
+    input_conversion: bool,
+    output_conversion: bool,
+    parse_mode: ParseMode,
+    compound_limits: Option<CompoundLimits>,
+    /// File listing the roots compounds may end in.
+    compound_heads: Option<String>,
+    provenance: bool,
+    format: OutputFormat,
+}
+
+/// How unmunch, `--expand` and `--find-base` modes print the words they generate.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum OutputFormat {
+    /// One word per line.
+    Plain,
+    /// One JSON object per word, with its base word and that entry's flags.
+    Jsonl,
+    /// The word and its lemma, separated by a tab.
+    Tsv,
+    /// The input word or dictionary entry, a tab, then its forms separated by spaces.
+    Grouped,
+}
+
+impl Options {
+    fn from_matches(matches: &ArgMatches) -> Self {
+        Options {
+            recode: matches.get_flag("recode-output"),
+            input_conversion: !matches.get_flag("no-iconv"),
+            output_conversion: !matches.get_flag("no-oconv"),
+            parse_mode: if matches.get_flag("lenient") { ParseMode::Lenient } else { ParseMode::Strict },
+            compound_limits: matches.try_get_one::<usize>("compounds").ok().flatten().map(|&max_parts| {
+                CompoundLimits::new(max_parts, *matches.get_one::<usize>("compound-max-length").unwrap())
+            }),
+            compound_heads: matches.try_get_one::<String>("compound-heads").ok().flatten().cloned(),
+            provenance: matches.try_get_one::<bool>("provenance").ok().flatten().copied().unwrap_or(false),
+            format: match matches.try_get_one::<String>("format").ok().flatten().map(String::as_str) {
+                Some("jsonl") => OutputFormat::Jsonl,
+                Some("tsv") => OutputFormat::Tsv,
+                Some("grouped") => OutputFormat::Grouped,
+                _ => OutputFormat::Plain,
+            },
+        }
+    }
+
+    /// Whether the entry each word was generated from is needed for the output.
+    fn traces_forms(&self) -> bool {
+        self.provenance || self.format != OutputFormat::Plain
+    }
+
+    fn load_runmunch(&self, affix_file: &str, dictionary_file: &str) -> Result<Runmunch, Box<dyn std::error::Error>> {
+        let mut runmunch = Runmunch::new();
+        runmunch.set_parse_mode(self.parse_mode);
+        runmunch.load_affix_file(affix_file)?;
+        runmunch.load_dictionary(dictionary_file)?;
+        runmunch.set_input_conversion(self.input_conversion);
+        runmunch.set_output_conversion(self.output_conversion);
+        report_diagnostics(runmunch.diagnostics());
+        Ok(runmunch)
+    }
+}
+
+fn report_diagnostics<'a>(diagnostics: impl IntoIterator<Item = &'a Diagnostic>) {
+    for diagnostic in diagnostics {
+        eprintln!("{}", diagnostic);
+    }
+}
+
+/// Writes one output word, encoded in `charset` when output recoding is requested.
+fn write_word(out: &mut impl Write, word: &str, charset: Option<&str>) -> Result<(), Box<dyn std::error::Error>> {
+    match charset {
+        Some(charset) => out.write_all(&encoding::encode(word, charset)?)?,
+        None => out.write_all(word.as_bytes())?,
+    }
+    out.write_all(b"\n")?;
+    Ok(())
+}
+
+/// `entry` as its word, then `/` and its flags if it has any.
+fn entry_field(entry: &DictionaryEntry, affix: &AffixFile) -> String {
+    if entry.flags.is_empty() {
+        entry.word.clone()
+    } else {
+        format!("{}/{}", entry.word, affix.flag_type.join(&entry.flags))
+    }
+}
+
+/// The entry's `st:` field, or its word.
+fn lemma(entry: &DictionaryEntry, affix: &AffixFile) -> String {
+    affix.expand_morph(&entry.morph).iter()
+        .find_map(|field| field.strip_prefix("st:"))
+        .unwrap_or(&entry.word)
+        .to_string()
+}
+
+/// The rules applied (or `-`) and `cross` (or `-`), separated by a tab.
+fn provenance_columns(form: &DerivedForm) -> String {
+    let rules = if form.rules.is_empty() {
+        "-".to_string()
+    } else {
+        form.rules.iter().map(ToString::to_string).collect::<Vec<_>>().join(" ")
+    };
+    let cross_product = if form.cross_product { "cross" } else { "-" };
+    format!("{}\t{}", rules, cross_product)
+}
+
+/// `s` as a JSON string literal.
+fn json_string(s: &str) -> String {
+    let mut json = String::with_capacity(s.len() + 2);
+    json.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if c < ' ' => json.push_str(&format!("\\u{:04x}", c as u32)),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+/// The line printed for one generated form in the plain, JSON Lines or TSV format.
+fn form_record(form: &DerivedForm, affix: &AffixFile, options: &Options) -> String {
+    match options.format {
+        OutputFormat::Jsonl => {
+            let flags: Vec<String> = form.entry.flags.iter().map(|flag| json_string(flag)).collect();
+            let mut record = format!(
+                "{{\"word\":{},\"base\":{},\"flags\":[{}]",
+                json_string(&form.word), json_string(&form.entry.word), flags.join(","),
+            );
+            if options.provenance {
+                let rules: Vec<String> = form.rules.iter().map(|rule| json_string(&rule.to_string())).collect();
+                record.push_str(&format!(",\"rules\":[{}],\"cross_product\":{}", rules.join(","), form.cross_product));
+            }
+            record.push('}');
+            record
+        }
+        OutputFormat::Tsv if options.provenance => {
+            format!("{}\t{}\t{}", form.word, lemma(&form.entry, affix), provenance_columns(form))
+        }
+        OutputFormat::Tsv => format!("{}\t{}", form.word, lemma(&form.entry, affix)),
+        _ if options.provenance => {
+            format!("{}\t{}\t{}", form.word, entry_field(&form.entry, affix), provenance_columns(form))
+        }
+        _ => form.word.clone(),
+    }
+}
+
+/// Writes the forms generated from `source`, an input word or dictionary entry: one line per form,
+/// or a single line in the grouped format.
+fn write_forms(
+    out: &mut impl Write,
+    source: &str,
+    forms: &[DerivedForm],
+    affix: &AffixFile,
+    options: &Options,
+    charset: Option<&str>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if options.format == OutputFormat::Grouped {
+        let mut words: Vec<&str> = Vec::new();
+        for form in forms {
+            if !words.contains(&form.word.as_str()) {
+                words.push(&form.word);
+            }
+        }
+        return write_word(out, &format!("{}\t{}", source, words.join(" ")), charset);
+    }
+    for form in forms {
+        write_word(out, &form_record(form, affix, options), charset)?;
+    }
+    Ok(())
+}
+
+/// The non-empty lines of `reader`, trimmed.
+fn read_words(reader: impl Read) -> io::Result<Vec<String>> {
+    let mut words = Vec::new();
+    for line in BufReader::new(reader).lines() {
+        let word = line?.trim().to_string();
+        if !word.is_empty() {
+            words.push(word);
+        }
+    }
+    Ok(words)
+}
+
+fn output_charset(affix: Option<&AffixFile>, options: &Options) -> Option<String> {
+    if options.recode {
+        affix.and_then(|affix| affix.encoding.clone())
+    } else {
+        None
+    }
+}
+
+fn run_expand_mode(affix_file: &str, dictionary_file: Option<&String>, options: &Options) -> Result<(), Box<dyn std::error::Error>> {
+    let affix = AffixFile::load_with_mode(affix_file, options.parse_mode)?;
+    report_diagnostics(&affix.diagnostics);
     let dictionary = if let Some(dict_path) = dictionary_file {
-        Some(runmunch::Dictionary::load(dict_path)?)
+        let dictionary = runmunch::Dictionary::load_with_mode(dict_path, Some(&affix), options.parse_mode)?;
+        report_diagnostics(dictionary.diagnostics());
+        Some(dictionary)
     } else {
         None
     };
+    let charset = output_charset(Some(&affix), options);
+    let mut out = io::stdout().lock();
     
     let mut expander = WordExpander::new();
     expander.set_affix_file(&affix);
+    let forbidden = match dictionary {
+        Some(ref dict) => expander.forbidden_forms(dict)?,
+        None => Default::default(),
+    };
     
     let stdin = io::stdin();
     let reader = BufReader::new(stdin.lock());
     
     for line in reader.lines() {
-        let word = line?.trim().to_string();
-        if !word.is_empty() {
-            let expanded = if let Some(ref dict) = dictionary {
-                // Look up the word in the dictionary to get its flags
-                if let Some(entry) = dict.get_entry(&word) {
-                    let expanded_flags = affix.expand_flags(&entry.flags);
-                    expander.expand_with_flags(&word, &expanded_flags)?
+        let input = line?.trim().to_string();
+        if !input.is_empty() {
+            let word = if options.input_conversion { affix.iconv.convert(&input) } else { input.clone() };
+            if options.traces_forms() {
+                let mut forms = Vec::new();
+                if let Some(ref dict) = dictionary {
+                    for entry in dict.get_entries(&word) {
+                        forms.extend(expander.expand_with_provenance(entry)?);
+                    }
+                    if forms.is_empty() {
+                        let entry = DictionaryEntry { word: word.clone(), flags: Vec::new(), morph: Vec::new() };
+                        forms.push(DerivedForm { word: word.clone(), entry, rules: Vec::new(), cross_product: false });
+                    }
                 } else {
+                    forms = expander.expand_all_with_provenance(&word)?;
+                }
+                forms.retain(|form| !forbidden.contains(&form.word));
+                if options.output_conversion {
+                    for form in &mut forms {
+                        form.word = affix.oconv.convert(&form.word);
+                    }
+                }
+                write_forms(&mut out, &input, &forms, &affix, options, charset.as_deref())?;
+                continue;
+            }
+            let expanded = if let Some(ref dict) = dictionary {
+                // Look up the word in the dictionary to get the flags of each of its entries
+                let mut expanded = Vec::new();
+                for entry in dict.get_entries(&word) {
+                    for form in expander.expand_with_flags(&word, &entry.flags)? {
+                        if !expanded.contains(&form) {
+                            expanded.push(form);
+                        }
+                    }
+                }
+                if expanded.is_empty() {
                     // Word not in dictionary, just return it as-is
-                    vec![word.clone()]
+                    expanded.push(word.clone());
                 }
+                expanded
             } else {
                 // No dictionary provided, just try to expand without flags
                 expander.expand(&word)?
             };
             
             for expanded_word in expanded {
-                println!("{}", expanded_word);
+                if forbidden.contains(&expanded_word) {
+                    continue;
+                }
+                let expanded_word = if options.output_conversion { affix.oconv.convert(&expanded_word) } else { expanded_word };
+                write_word(&mut out, &expanded_word, charset.as_deref())?;
             }
         }
     }
@@ -102,10 +485,10 @@ fn run_expand_mode(affix_file: &str, dictionary_file: Option<&String>) -> Result
     Ok(())
 }
 
-fn run_find_base_mode(affix_file: &str, dictionary_file: &str) -> Result<(), Box<dyn std::error::Error>> {
-    let mut runmunch = Runmunch::new();
-    runmunch.load_affix_file(affix_file)?;
-    runmunch.load_dictionary(dictionary_file)?;
+fn run_find_base_mode(affix_file: &str, dictionary_file: &str, options: &Options) -> Result<(), Box<dyn std::error::Error>> {
+    let runmunch = options.load_runmunch(affix_file, dictionary_file)?;
+    let charset = output_charset(runmunch.affix_file(), options);
+    let mut out = io::stdout().lock();
 
     let stdin = io::stdin();
     let reader = BufReader::new(stdin.lock());
@@ -113,9 +496,14 @@ fn run_find_base_mode(affix_file: &str, dictionary_file: &str) -> Result<(), Box
     for line in reader.lines() {
         let word = line?.trim().to_string();
         if !word.is_empty() {
+            if options.traces_forms() {
+                let forms = runmunch.find_base_with_provenance(&word)?;
+                write_forms(&mut out, &word, &forms, runmunch.affix_file().unwrap(), options, charset.as_deref())?;
+                continue;
+            }
             let expanded = runmunch.find_base_and_expand(&word)?;
             for expanded_word in expanded {
-                println!("{}", expanded_word);
+                write_word(&mut out, &expanded_word, charset.as_deref())?;
             }
         }
     }
@@ -123,17 +511,153 @@ fn run_find_base_mode(affix_file: &str, dictionary_file: &str) -> Result<(), Box
     Ok(())
 }
 
-fn run_unmunch_mode(affix_file: &str, dictionary_file: &str) -> Result<(), Box<dyn std::error::Error>> {
-    let mut runmunch = Runmunch::new();
+fn run_unmunch_mode(affix_file: &str, dictionary_file: &str, options: &Options) -> Result<(), Box<dyn std::error::Error>> {
+    let runmunch = options.load_runmunch(affix_file, dictionary_file)?;
+    let charset = output_charset(runmunch.affix_file(), options);
     
-    runmunch.load_affix_file(affix_file)?;
-    runmunch.load_dictionary(dictionary_file)?;
-    
-    let expanded_words = runmunch.unmunch()?;
+    let mut out = io::stdout().lock();
+
+    let affix = runmunch.affix_file().unwrap();
+    if options.traces_forms() {
+        for entry in runmunch.dictionary().unwrap().iter() {
+            let forms = runmunch.expand_entry_with_provenance(entry)?;
+            write_forms(&mut out, &entry_field(entry, affix), &forms, affix, options, charset.as_deref())?;
+        }
+    } else {
+        for word in runmunch.unmunch()? {
+            write_word(&mut out, &word, charset.as_deref())?;
+        }
+    }
     
-    for word in expanded_words {
-        println!("{}", word);
+    if let Some(ref limits) = options.compound_limits {
+        let limits = match &options.compound_heads {
+            Some(path) => limits.clone().with_heads(read_words(File::open(path)?)?),
+            None => limits.clone(),
+        };
+        for word in runmunch.unmunch_compounds(&limits)? {
+            if options.traces_forms() {
+                // A compound has no single base entry, so it stands for itself
+                let entry = DictionaryEntry { word: word.clone(), flags: Vec::new(), morph: Vec::new() };
+                let form = DerivedForm { word: word.clone(), entry, rules: Vec::new(), cross_product: false };
+                write_forms(&mut out, &word, &[form], affix, options, charset.as_deref())?;
+            } else {
+                write_word(&mut out, &word, charset.as_deref())?;
+            }
+        }
     }
     
     Ok(())
 }
+
+fn run_munch_mode(
+    affix_file: &str,
+    words_file: Option<&String>,
+    affix_output: Option<&String>,
+    options: &Options,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut runmunch = Runmunch::new();
+    runmunch.set_parse_mode(options.parse_mode);
+    runmunch.load_affix_file(affix_file)?;
+    runmunch.set_input_conversion(options.input_conversion);
+    report_diagnostics(runmunch.diagnostics());
+
+    let words = match words_file {
+        Some(path) => read_words(File::open(path)?)?,
+        None => read_words(io::stdin())?,
+    };
+
+    let dictionary = runmunch.munch(&words)?;
+    let mut affix = runmunch.affix_file().unwrap().clone();
+    let content = dictionary.serialize_with_aliases(&mut affix)?;
+    match affix_output {
+        Some(path) => affix.save(path)?,
+        None if affix.flag_aliases.len() > runmunch.affix_file().unwrap().flag_aliases.len() => {
+            return Err("The dictionary needs AF aliases the affix file lacks; write it with --affix-output".into());
+        }
+        None => {}
+    }
+    let charset = output_charset(runmunch.affix_file(), options);
+    let mut out = io::stdout().lock();
+    match charset {
+        Some(charset) => out.write_all(&encoding::encode(&content, &charset)?)?,
+        None => out.write_all(content.as_bytes())?,
+    }
+
+    Ok(())
+}
+
+fn run_suggest_mode(affix_file: &str, dictionary_file: &str, options: &Options) -> Result<(), Box<dyn std::error::Error>> {
+    let runmunch = options.load_runmunch(affix_file, dictionary_file)?;
+    let charset = output_charset(runmunch.affix_file(), options);
+    let mut out = io::stdout().lock();
+
+    let stdin = io::stdin();
+    let reader = BufReader::new(stdin.lock());
+
+    for line in reader.lines() {
+        let word = line?.trim().to_string();
+        if word.is_empty() {
+            continue;
+        }
+        let result = if runmunch.check(&word)? {
+            "*".to_string()
+        } else {
+            let suggestions = runmunch.suggest(&word)?;
+            if suggestions.is_empty() {
+                format!("# {}", word)
+            } else {
+                format!("& {}: {}", word, suggestions.join(", "))
+            }
+        };
+        write_word(&mut out, &result, charset.as_deref())?;
+    }
+
+    Ok(())
+}
+
+fn run_analyze_mode(affix_file: &str, dictionary_file: &str, options: &Options) -> Result<(), Box<dyn std::error::Error>> {
+    let runmunch = options.load_runmunch(affix_file, dictionary_file)?;
+    let charset = output_charset(runmunch.affix_file(), options);
+    let mut out = io::stdout().lock();
+
+    let stdin = io::stdin();
+    let reader = BufReader::new(stdin.lock());
+
+    for line in reader.lines() {
+        let word = line?.trim().to_string();
+        if word.is_empty() {
+            continue;
+        }
+        for analysis in runmunch.analyze(&word)? {
+            write_word(&mut out, &format!("{} {}", word, analysis.morph.join(" ")), charset.as_deref())?;
+        }
+        write_word(&mut out, "", charset.as_deref())?;
+    }
+
+    Ok(())
+}
+
+fn run_stem_mode(affix_file: &str, dictionary_file: &str, options: &Options) -> Result<(), Box<dyn std::error::Error>> {
+    let runmunch = options.load_runmunch(affix_file, dictionary_file)?;
+    let charset = output_charset(runmunch.affix_file(), options);
+    let mut out = io::stdout().lock();
+
+    let stdin = io::stdin();
+    let reader = BufReader::new(stdin.lock());
+
+    for line in reader.lines() {
+        let word = line?.trim().to_string();
+        if word.is_empty() {
+            continue;
+        }
+        let stems = runmunch.stem(&word)?;
+        if stems.is_empty() {
+            write_word(&mut out, &word, charset.as_deref())?;
+        }
+        for stem in stems {
+            write_word(&mut out, &format!("{} {}", word, stem), charset.as_deref())?;
+        }
+    }
+
+    Ok(())
+}
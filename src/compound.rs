@@ -0,0 +1,257 @@
+use crate::affix::FlagType;
+use crate::error::{Result, RunmunchError};
+use std::collections::HashSet;
+
+/// Default minimum length of a compound part (`COMPOUNDMIN`).
+pub const DEFAULT_COMPOUND_MIN: usize = 3;
+
+/// Compounding directives of an affix file.
+#[derive(Debug, Clone)]
+pub struct CompoundOptions {
+    /// `COMPOUNDFLAG`: words with this flag may appear anywhere in a compound.
+    pub flag: Option<String>,
+    /// `COMPOUNDBEGIN`: words with this flag may start a compound.
+    pub begin: Option<String>,
+    /// `COMPOUNDMIDDLE`: words with this flag may appear inside a compound.
+    pub middle: Option<String>,
+    /// `COMPOUNDEND`: words with this flag may end a compound.
+    pub end: Option<String>,
+    /// `COMPOUNDMIN`: minimum length of each part, in characters.
+    pub min_length: usize,
+    /// `COMPOUNDWORDMAX`: maximum number of parts in a compound.
+    pub word_max: Option<usize>,
+    /// `ONLYINCOMPOUND`: words and affixes with this flag appear only inside compounds.
+    pub only_in_compound: Option<String>,
+    /// `COMPOUNDPERMITFLAG`: affixes with this flag may be used inside compounds.
+    pub permit: Option<String>,
+    /// `COMPOUNDFORBIDFLAG`: affixed forms with this flag may not be used in compounds.
+    pub forbid: Option<String>,
+    /// `COMPOUNDRULE` patterns over the flags of the parts.
+    pub rules: Vec<CompoundRule>,
+}
+
+impl CompoundOptions {
+    pub fn new() -> Self {
+        CompoundOptions {
+            flag: None,
+            begin: None,
+            middle: None,
+            end: None,
+            min_length: DEFAULT_COMPOUND_MIN,
+            word_max: None,
+            only_in_compound: None,
+            permit: None,
+            forbid: None,
+            rules: Vec::new(),
+        }
+    }
+
+    /// Whether the affix file enables flag-based compounding.
+    pub fn is_enabled(&self) -> bool {
+        self.flag.is_some() || self.begin.is_some() || self.middle.is_some() || self.end.is_some()
+    }
+
+    /// Whether `flags` allow a word at the given position of a compound.
+    pub fn allows(&self, position: CompoundPosition, flags: &[String]) -> bool {
+        let positional = match position {
+            CompoundPosition::Begin => &self.begin,
+            CompoundPosition::Middle => &self.middle,
+            CompoundPosition::End => &self.end,
+        };
+        [&self.flag, positional]
+            .into_iter()
+            .any(|directive| directive.as_ref().is_some_and(|flag| flags.contains(flag)))
+    }
+
+    pub fn permits(&self, flags: &[String]) -> bool {
+        self.permit.as_ref().is_some_and(|flag| flags.contains(flag))
+    }
+
+    pub fn forbids(&self, flags: &[String]) -> bool {
+        self.forbid.as_ref().is_some_and(|flag| flags.contains(flag))
+    }
+}
+
+impl Default for CompoundOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Quantifier {
+    One,
+    /// `?`
+    ZeroOrOne,
+    /// `*`
+    ZeroOrMore,
+}
+
+/// A `COMPOUNDRULE` pattern such as `n*1t`: a sequence of flags, each optionally followed by
+/// `?` or `*`. Multi-character flags (`FLAG long`/`num`) are written in parentheses: `(aa)*(bb)`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompoundRule {
+    pub pattern: String,
+    pub elements: Vec<(String, Quantifier)>,
+}
+
+impl CompoundRule {
+    pub fn parse(pattern: &str, flag_type: &FlagType) -> Result<Self> {
+        let mut elements: Vec<(String, Quantifier)> = Vec::new();
+        let mut chars = pattern.chars();
+
+        while let Some(c) = chars.next() {
+            let quantifier = match c {
+                '*' => Some(Quantifier::ZeroOrMore),
+                '?' => Some(Quantifier::ZeroOrOne),
+                _ => None,
+            };
+            if let Some(quantifier) = quantifier {
+                match elements.last_mut() {
+                    Some(element) if element.1 == Quantifier::One => element.1 = quantifier,
+                    _ => return Err(RunmunchError::InvalidAffix(format!("Misplaced quantifier in COMPOUNDRULE: {}", pattern))),
+                }
+                continue;
+            }
+
+            let flag = if c == '(' {
+                let mut flag = String::new();
+                loop {
+                    match chars.next() {
+                        Some(')') => break,
+                        Some(c) => flag.push(c),
+                        None => return Err(RunmunchError::InvalidAffix(format!("Unclosed parenthesis in COMPOUNDRULE: {}", pattern))),
+                    }
+                }
+                if flag.is_empty() {
+                    return Err(RunmunchError::InvalidAffix(format!("Empty flag in COMPOUNDRULE: {}", pattern)));
+                }
+                flag
+            } else {
+                match flag_type {
+                    FlagType::Single | FlagType::Utf8 => c.to_string(),
+                    FlagType::Long | FlagType::Numeric => {
+                        return Err(RunmunchError::InvalidAffix(format!("COMPOUNDRULE flags must be parenthesized: {}", pattern)));
+                    }
+                }
+            };
+            elements.push((flag, Quantifier::One));
+        }
+
+        Ok(CompoundRule {
+            pattern: pattern.to_string(),
+            elements,
+        })
+    }
+
+    /// Whether some flag of every part, in order, matches the pattern as a whole.
+    pub fn matches(&self, parts: &[&[String]]) -> bool {
+        self.matches_from(0, parts)
+    }
+
+    fn matches_from(&self, element: usize, parts: &[&[String]]) -> bool {
+        let Some((flag, quantifier)) = self.elements.get(element) else {
+            return parts.is_empty();
+        };
+        let first_matches = parts.first().is_some_and(|flags| flags.contains(flag));
+
+        match quantifier {
+            Quantifier::One => first_matches && self.matches_from(element + 1, &parts[1..]),
+            Quantifier::ZeroOrOne => {
+                self.matches_from(element + 1, parts)
+                    || (first_matches && self.matches_from(element + 1, &parts[1..]))
+            }
+            Quantifier::ZeroOrMore => {
+                self.matches_from(element + 1, parts)
+                    || (first_matches && self.matches_from(element, &parts[1..]))
+            }
+        }
+    }
+
+    /// Flags the pattern refers to.
+    pub fn flags(&self) -> impl Iterator<Item = &String> {
+        self.elements.iter().map(|(flag, _)| flag)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompoundPosition {
+    Begin,
+    Middle,
+    End,
+}
+
+/// Bounds for compound generation, which is otherwise combinatorially unbounded.
+#[derive(Debug, Clone)]
+pub struct CompoundLimits {
+    /// Maximum number of parts; `COMPOUNDWORDMAX` lowers it further when set.
+    pub max_parts: usize,
+    /// Maximum length of a generated compound, in characters.
+    pub max_length: usize,
+    /// When set, only compounds whose last part derives from one of these roots are generated.
+    pub heads: Option<HashSet<String>>,
+}
+
+impl CompoundLimits {
+    pub fn new(max_parts: usize, max_length: usize) -> Self {
+        CompoundLimits {
+            max_parts,
+            max_length,
+            heads: None,
+        }
+    }
+
+    pub fn with_heads<I: IntoIterator<Item = String>>(mut self, heads: I) -> Self {
+        self.heads = Some(heads.into_iter().collect());
+        self
+    }
+}
+
+impl Default for CompoundLimits {
+    fn default() -> Self {
+        Self::new(2, 30)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn flags(list: &[&str]) -> Vec<String> {
+        list.iter().map(|flag| flag.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse_compound_rule() {
+        let rule = CompoundRule::parse("n*1t", &FlagType::Single).unwrap();
+        assert_eq!(rule.elements, vec![
+            ("n".to_string(), Quantifier::ZeroOrMore),
+            ("1".to_string(), Quantifier::One),
+            ("t".to_string(), Quantifier::One),
+        ]);
+
+        let rule = CompoundRule::parse("(aa)?(bb)", &FlagType::Long).unwrap();
+        assert_eq!(rule.elements, vec![
+            ("aa".to_string(), Quantifier::ZeroOrOne),
+            ("bb".to_string(), Quantifier::One),
+        ]);
+
+        assert!(CompoundRule::parse("*a", &FlagType::Single).is_err());
+        assert!(CompoundRule::parse("aabb", &FlagType::Long).is_err());
+        let error = CompoundRule::parse("(aa)(bb", &FlagType::Long).unwrap_err();
+        assert!(error.to_string().contains("Unclosed parenthesis"));
+    }
+
+    #[test]
+    fn test_compound_rule_matches() {
+        let rule = CompoundRule::parse("n*1t", &FlagType::Single).unwrap();
+        let number = flags(&["n", "1"]);
+        let ordinal = flags(&["t"]);
+
+        assert!(rule.matches(&[&number, &ordinal]));
+        assert!(rule.matches(&[&number, &number, &number, &ordinal]));
+        assert!(!rule.matches(&[&ordinal, &number]));
+        assert!(!rule.matches(&[&number]));
+    }
+}
+
@@ -0,0 +1,130 @@
+use crate::error::{Result, RunmunchError};
+
+/// One character position of an affix condition.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConditionPosition {
+    /// `.`: any character.
+    Any,
+    /// A literal character.
+    Char(char),
+    /// `[abc]`, or `[^abc]` when negated.
+    Class { chars: Vec<char>, negated: bool },
+}
+
+impl ConditionPosition {
+    fn matches(&self, c: char) -> bool {
+        match self {
+            ConditionPosition::Any => true,
+            ConditionPosition::Char(expected) => c == *expected,
+            ConditionPosition::Class { chars, negated } => chars.contains(&c) != *negated,
+        }
+    }
+}
+
+/// An affix condition such as `[^aeiou]y`: a sequence of character positions that must match the
+/// start of the word for prefixes and the end of the word for suffixes, before the strip is
+/// removed. `.` on its own means no condition.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Condition {
+    pub positions: Vec<ConditionPosition>,
+}
+
+impl Condition {
+    pub fn parse(condition_str: &str) -> Result<Self> {
+        if condition_str == "." {
+            return Ok(Condition::default());
+        }
+
+        let mut positions = Vec::new();
+        let mut chars = condition_str.chars();
+
+        while let Some(c) = chars.next() {
+            let position = match c {
+                '.' => ConditionPosition::Any,
+                '[' => {
+                    let mut class = Vec::new();
+                    let mut closed = false;
+                    for c in chars.by_ref() {
+                        if c == ']' {
+                            closed = true;
+                            break;
+                        }
+                        class.push(c);
+                    }
+                    if !closed {
+                        return Err(RunmunchError::InvalidAffix(format!("Unclosed bracket in condition: {}", condition_str)));
+                    }
+                    let negated = class.first() == Some(&'^');
+                    if negated {
+                        class.remove(0);
+                    }
+                    if class.is_empty() {
+                        return Err(RunmunchError::InvalidAffix(format!("Empty character class in condition: {}", condition_str)));
+                    }
+                    ConditionPosition::Class { chars: class, negated }
+                }
+                ']' => {
+                    return Err(RunmunchError::InvalidAffix(format!("Unopened bracket in condition: {}", condition_str)));
+                }
+                c => ConditionPosition::Char(c),
+            };
+            positions.push(position);
+        }
+
+        Ok(Condition { positions })
+    }
+
+    /// Whether the condition places no constraint on the word.
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+
+    /// Number of characters the condition examines.
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    /// Whether the first characters of `word` match the condition (prefix rules).
+    pub fn matches_start(&self, word: &str) -> bool {
+        let mut chars = word.chars();
+        self.positions.iter().all(|position| chars.next().is_some_and(|c| position.matches(c)))
+    }
+
+    /// Whether the last characters of `word` match the condition (suffix rules).
+    pub fn matches_end(&self, word: &str) -> bool {
+        let mut chars = word.chars().rev();
+        self.positions.iter().rev().all(|position| chars.next().is_some_and(|c| position.matches(c)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_condition() {
+        let condition = Condition::parse("[^aeiou]y").unwrap();
+        assert_eq!(condition.positions, vec![
+            ConditionPosition::Class { chars: vec!['a', 'e', 'i', 'o', 'u'], negated: true },
+            ConditionPosition::Char('y'),
+        ]);
+        assert!(Condition::parse(".").unwrap().is_empty());
+        assert_eq!(Condition::parse("a.").unwrap().len(), 2);
+        assert!(Condition::parse("[ab").is_err());
+        assert!(Condition::parse("a]").is_err());
+        assert!(Condition::parse("[]").is_err());
+    }
+
+    #[test]
+    fn test_condition_matches() {
+        let suffix = Condition::parse("[^aeiou]y").unwrap();
+        assert!(suffix.matches_end("fly"));
+        assert!(!suffix.matches_end("boy"));
+        assert!(!suffix.matches_end("y"));
+
+        let prefix = Condition::parse("[^u]ć").unwrap();
+        assert!(prefix.matches_start("aćb"));
+        assert!(!prefix.matches_start("ućb"));
+        assert!(!prefix.matches_start("baća"));
+    }
+}
@@ -0,0 +1,103 @@
+/// A character conversion table (`ICONV` or `OCONV`).
+///
+/// Conversions are applied left to right, always replacing the longest matching pattern. A
+/// pattern starting or ending with `_` only matches at the start or end of the word.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ConversionTable {
+    pub entries: Vec<(String, String)>,
+}
+
+impl ConversionTable {
+    pub fn new() -> Self {
+        ConversionTable { entries: Vec::new() }
+    }
+
+    pub fn add(&mut self, pattern: &str, replacement: &str) {
+        self.entries.push((pattern.to_string(), replacement.to_string()));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn convert(&self, word: &str) -> String {
+        if self.entries.is_empty() {
+            return word.to_string();
+        }
+
+        let mut result = String::with_capacity(word.len());
+        let mut position = 0;
+
+        while position < word.len() {
+            let rest = &word[position..];
+            let matched = self.entries.iter()
+                .filter_map(|(pattern, replacement)| {
+                    Self::match_at(pattern, word, position).map(|length| (length, replacement))
+                })
+                .max_by_key(|(length, _)| *length);
+
+            match matched {
+                Some((length, replacement)) if length > 0 => {
+                    result.push_str(replacement);
+                    position += length;
+                }
+                _ => {
+                    let c = rest.chars().next().unwrap_or_default();
+                    result.push(c);
+                    position += c.len_utf8();
+                }
+            }
+        }
+
+        result
+    }
+
+    /// Length in bytes of the text `pattern` matches at `position`, honouring `_` anchors.
+    fn match_at(pattern: &str, word: &str, position: usize) -> Option<usize> {
+        let (anchored_start, pattern) = match pattern.strip_prefix('_') {
+            Some(rest) if !rest.is_empty() => (true, rest),
+            _ => (false, pattern),
+        };
+        let (anchored_end, pattern) = match pattern.strip_suffix('_') {
+            Some(rest) if !rest.is_empty() => (true, rest),
+            _ => (false, pattern),
+        };
+
+        if anchored_start && position != 0 {
+            return None;
+        }
+        if !word[position..].starts_with(pattern) {
+            return None;
+        }
+        if anchored_end && position + pattern.len() != word.len() {
+            return None;
+        }
+        Some(pattern.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_longest_match_wins() {
+        let mut table = ConversionTable::new();
+        table.add("’", "'");
+        table.add("ij", "ĳ");
+        table.add("i", "I");
+
+        assert_eq!(table.convert("rijk’s"), "rĳk's");
+        assert_eq!(table.convert("pit"), "pIt");
+    }
+
+    #[test]
+    fn test_anchored_patterns() {
+        let mut table = ConversionTable::new();
+        table.add("_a", "A");
+        table.add("a_", "Z");
+
+        assert_eq!(table.convert("banana"), "bananZ");
+        assert_eq!(table.convert("aba"), "AbZ");
+    }
+}
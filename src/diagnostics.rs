@@ -0,0 +1,198 @@
+use crate::error::{Result, RunmunchError};
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// The input is suspicious but was understood.
+    Warning,
+    /// The offending line could not be used.
+    Error,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiagnosticCode {
+    /// A `PFX`/`SFX` header declares a different number of rules than follow it.
+    RuleCountMismatch,
+    /// The dictionary holds a different number of entries than its first line declares.
+    EntryCountMismatch,
+    /// A line starts with a directive hunspell does not know.
+    UnknownDirective,
+    /// A second `PFX`/`SFX` block uses a flag that already has a block; its rules are merged.
+    DuplicateFlagBlock,
+    /// An affix condition cannot be parsed.
+    BadCondition,
+    /// A flag string does not match the `FLAG` type or refers to an undefined alias.
+    InvalidFlag,
+    /// A directive value, count or dictionary entry is malformed.
+    InvalidValue,
+}
+
+impl DiagnosticCode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DiagnosticCode::RuleCountMismatch => "rule-count-mismatch",
+            DiagnosticCode::EntryCountMismatch => "entry-count-mismatch",
+            DiagnosticCode::UnknownDirective => "unknown-directive",
+            DiagnosticCode::DuplicateFlagBlock => "duplicate-flag-block",
+            DiagnosticCode::BadCondition => "bad-condition",
+            DiagnosticCode::InvalidFlag => "invalid-flag",
+            DiagnosticCode::InvalidValue => "invalid-value",
+        }
+    }
+}
+
+/// A problem found while parsing an affix or dictionary file. Lines and columns count from 1;
+/// columns are in characters.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub file: Option<String>,
+    pub line: usize,
+    pub column: usize,
+    pub severity: Severity,
+    pub code: DiagnosticCode,
+    pub message: String,
+}
+
+impl Diagnostic {
+    /// `file:line:column`, or `line L, column C` when the input did not come from a file.
+    pub fn location(&self) -> String {
+        match &self.file {
+            Some(file) => format!("{}:{}:{}", file, self.line, self.column),
+            None => format!("line {}, column {}", self.line, self.column),
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        };
+        write!(f, "{}: {}[{}]: {}", self.location(), severity, self.code.as_str(), self.message)
+    }
+}
+
+/// How parsing reacts to errors. Warnings never stop parsing and are always collected.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ParseMode {
+    /// The first error aborts parsing.
+    #[default]
+    Strict,
+    /// Errors are collected and the offending line is skipped.
+    Lenient,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Source {
+    Affix,
+    Dictionary,
+}
+
+/// Collects the diagnostics of one file, turning errors into `RunmunchError`s in strict mode.
+#[derive(Debug)]
+pub(crate) struct Diagnostics {
+    file: Option<String>,
+    mode: ParseMode,
+    source: Source,
+    pub(crate) list: Vec<Diagnostic>,
+}
+
+impl Diagnostics {
+    pub(crate) fn new(file: Option<String>, mode: ParseMode, source: Source) -> Self {
+        Diagnostics {
+            file,
+            mode,
+            source,
+            list: Vec::new(),
+        }
+    }
+
+    fn diagnostic(&self, line: usize, column: usize, severity: Severity, code: DiagnosticCode, message: String) -> Diagnostic {
+        Diagnostic {
+            file: self.file.clone(),
+            line,
+            column,
+            severity,
+            code,
+            message,
+        }
+    }
+
+    pub(crate) fn warn(&mut self, line: usize, column: usize, code: DiagnosticCode, message: String) {
+        let diagnostic = self.diagnostic(line, column, Severity::Warning, code, message);
+        self.list.push(diagnostic);
+    }
+
+    /// Records an error, or returns it in strict mode.
+    pub(crate) fn error(&mut self, line: usize, column: usize, code: DiagnosticCode, message: String) -> Result<()> {
+        let diagnostic = self.diagnostic(line, column, Severity::Error, code, message);
+        if self.mode == ParseMode::Lenient {
+            self.list.push(diagnostic);
+            return Ok(());
+        }
+
+        let message = format!("{}: {}", diagnostic.location(), diagnostic.message);
+        Err(match (code, self.source) {
+            (DiagnosticCode::InvalidFlag, _) => RunmunchError::InvalidFlag(message),
+            (_, Source::Affix) => RunmunchError::InvalidAffix(message),
+            (_, Source::Dictionary) => RunmunchError::InvalidDictionary(message),
+        })
+    }
+
+    /// Records an error raised by a parsing helper, classified by its kind.
+    pub(crate) fn error_from(&mut self, line: usize, column: usize, error: RunmunchError) -> Result<()> {
+        let (code, message) = match error {
+            RunmunchError::InvalidFlag(message) => (DiagnosticCode::InvalidFlag, message),
+            RunmunchError::InvalidAffix(message) | RunmunchError::InvalidDictionary(message) => {
+                (DiagnosticCode::InvalidValue, message)
+            }
+            error => return Err(error),
+        };
+        self.error(line, column, code, message)
+    }
+}
+
+/// Column (1-based, in characters) of the whitespace-separated field `index` of `line`, or of the
+/// line's end when it has fewer fields.
+pub(crate) fn field_column(line: &str, index: usize) -> usize {
+    let mut fields = 0;
+    let mut in_field = false;
+    for (column, c) in line.chars().enumerate() {
+        if c.is_whitespace() {
+            in_field = false;
+        } else if !in_field {
+            if fields == index {
+                return column + 1;
+            }
+            fields += 1;
+            in_field = true;
+        }
+    }
+    line.chars().count() + 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_field_column() {
+        assert_eq!(field_column("SFX A Y 2", 0), 1);
+        assert_eq!(field_column("  SFX  A Y 2", 1), 8);
+        assert_eq!(field_column("SFX A Y", 3), 8);
+    }
+
+    #[test]
+    fn test_strict_and_lenient_errors() {
+        let mut strict = Diagnostics::new(Some("en.aff".to_string()), ParseMode::Strict, Source::Affix);
+        strict.warn(1, 1, DiagnosticCode::UnknownDirective, "Unknown directive FOO".to_string());
+        let error = strict.error(3, 9, DiagnosticCode::BadCondition, "Unclosed bracket".to_string()).unwrap_err();
+        assert_eq!(error.to_string(), "Invalid affix format: en.aff:3:9: Unclosed bracket");
+        assert_eq!(strict.list.len(), 1);
+
+        let mut lenient = Diagnostics::new(None, ParseMode::Lenient, Source::Dictionary);
+        lenient.error(2, 5, DiagnosticCode::InvalidFlag, "Odd number of characters".to_string()).unwrap();
+        assert_eq!(lenient.list[0].to_string(), "line 2, column 5: error[invalid-flag]: Odd number of characters");
+    }
+}
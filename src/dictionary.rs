@@ -1,74 +1,212 @@
+use crate::affix::AffixFile;
+use crate::diagnostics::{field_column, Diagnostic, DiagnosticCode, Diagnostics, ParseMode, Source};
+use crate::encoding;
 use crate::error::{Result, RunmunchError};
 use std::collections::HashMap;
 use std::fs;
 use std::path::Path;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct DictionaryEntry {
     pub word: String,
     pub flags: Vec<String>,
+    /// Morphological fields following the word (`po:noun st:dog`), or an `AM` alias number.
+    pub morph: Vec<String>,
 }
 
 #[derive(Debug, Clone)]
 pub struct Dictionary {
     entries: Vec<DictionaryEntry>,
-    word_to_entry: HashMap<String, usize>,
+    /// Indices of the entries of each word; homonyms have several.
+    word_to_entries: HashMap<String, Vec<usize>>,
+    diagnostics: Vec<Diagnostic>,
 }
 
 impl Dictionary {
     pub fn new() -> Self {
         Dictionary {
             entries: Vec::new(),
-            word_to_entry: HashMap::new(),
+            word_to_entries: HashMap::new(),
+            diagnostics: Vec::new(),
         }
     }
 
     pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
-        let content = fs::read_to_string(path)?;
-        Self::parse(&content)
+        Self::load_with_encoding(path, None)
+    }
+
+    /// Loads a dictionary stored in the given charset (as named by the affix file's `SET`
+    /// directive); `None` means UTF-8.
+    pub fn load_with_encoding<P: AsRef<Path>>(path: P, charset: Option<&str>) -> Result<Self> {
+        let bytes = fs::read(&path)?;
+        let content = encoding::decode(&bytes, charset)?;
+        Self::parse_source(&content, Some(path.as_ref().display().to_string()), None, ParseMode::Strict)
+    }
+
+    /// Loads a dictionary belonging to `affix_file`, decoding it with the affix file's `SET`
+    /// charset and splitting flags according to its `FLAG` type.
+    pub fn load_with_affix_file<P: AsRef<Path>>(path: P, affix_file: &AffixFile) -> Result<Self> {
+        Self::load_with_mode(path, Some(affix_file), ParseMode::Strict)
     }
 
+    /// Loads a dictionary, collecting warnings (and, in lenient mode, errors) in `diagnostics`.
+    pub fn load_with_mode<P: AsRef<Path>>(path: P, affix_file: Option<&AffixFile>, mode: ParseMode) -> Result<Self> {
+        let bytes = fs::read(&path)?;
+        let charset = affix_file.and_then(|affix_file| affix_file.encoding.as_deref());
+        let content = encoding::decode(&bytes, charset)?;
+        Self::parse_source(&content, Some(path.as_ref().display().to_string()), affix_file, mode)
+    }
+
+    /// Parses a dictionary without an affix file, guessing the flag format of each entry.
     pub fn parse(content: &str) -> Result<Self> {
+        Self::parse_with_mode(content, None, ParseMode::Strict)
+    }
+
+    /// Parses a dictionary belonging to `affix_file`, splitting flags according to its `FLAG`
+    /// type; malformed flag strings are reported as `RunmunchError::InvalidFlag`.
+    pub fn parse_with_affix_file(content: &str, affix_file: &AffixFile) -> Result<Self> {
+        Self::parse_with_mode(content, Some(affix_file), ParseMode::Strict)
+    }
+
+    /// Parses a dictionary, collecting warnings (and, in lenient mode, errors) in `diagnostics`.
+    pub fn parse_with_mode(content: &str, affix_file: Option<&AffixFile>, mode: ParseMode) -> Result<Self> {
+        Self::parse_source(content, None, affix_file, mode)
+    }
+
+    fn parse_source(content: &str, file: Option<String>, affix_file: Option<&AffixFile>, mode: ParseMode) -> Result<Self> {
         let mut dictionary = Dictionary::new();
+        let mut diagnostics = Diagnostics::new(file, mode, Source::Dictionary);
         let lines: Vec<&str> = content.lines().collect();
 
         if lines.is_empty() {
             return Err(RunmunchError::InvalidDictionary("Empty dictionary file".to_string()));
         }
 
-        let word_count: usize = lines[0].trim().parse()
-            .map_err(|_| RunmunchError::InvalidDictionary("Invalid word count".to_string()))?;
+        let word_count = match lines[0].trim().parse::<usize>() {
+            Ok(word_count) => Some(word_count),
+            Err(_) => {
+                diagnostics.error(1, 1, DiagnosticCode::InvalidValue, "Invalid word count".to_string())?;
+                None
+            }
+        };
 
-        for (_line_idx, line) in lines.iter().enumerate().skip(1) {
-            let line = line.trim();
+        for (line_idx, raw_line) in lines.iter().enumerate().skip(1) {
+            let line = raw_line.trim();
             if line.is_empty() {
                 continue;
             }
 
-            let (word, flags) = Self::parse_entry(line)?;
-            let entry = DictionaryEntry { word: word.clone(), flags };
+            let parsed = match affix_file {
+                Some(affix_file) => Self::parse_entry_with_affix_file(line, affix_file),
+                None => Self::parse_entry(line),
+            };
+            let (word, flags, morph) = match parsed {
+                Ok(parsed) => parsed,
+                Err(error) => {
+                    let column = match error {
+                        RunmunchError::InvalidFlag(_) => Self::flags_column(raw_line),
+                        _ => field_column(raw_line, raw_line.split_whitespace().count() - 1),
+                    };
+                    diagnostics.error_from(line_idx + 1, column, error)?;
+                    continue;
+                }
+            };
+            let entry = DictionaryEntry { word: word.clone(), flags, morph };
             
-            dictionary.word_to_entry.insert(word, dictionary.entries.len());
+            dictionary.word_to_entries.entry(word).or_default().push(dictionary.entries.len());
             dictionary.entries.push(entry);
         }
 
-        if dictionary.entries.len() > word_count {
-            eprintln!("Warning: Dictionary contains more entries ({}) than declared ({})", 
-                     dictionary.entries.len(), word_count);
+        if let Some(word_count) = word_count.filter(|&word_count| word_count != dictionary.entries.len()) {
+            let message = format!("Dictionary contains {} entries but declares {}", dictionary.entries.len(), word_count);
+            diagnostics.warn(1, 1, DiagnosticCode::EntryCountMismatch, message);
         }
 
+        dictionary.diagnostics = diagnostics.list;
         Ok(dictionary)
     }
 
-    fn parse_entry(line: &str) -> Result<(String, Vec<String>)> {
-        if let Some(slash_pos) = line.find('/') {
-            let word = line[..slash_pos].trim().to_string();
-            let flags_str = line[slash_pos + 1..].trim();
-            let flags = Self::parse_flags(flags_str);
-            Ok((word, flags))
-        } else {
-            Ok((line.trim().to_string(), Vec::new()))
+    /// Column of the flag string of an entry line: the character after the first unescaped `/`.
+    fn flags_column(line: &str) -> usize {
+        let mut escaped = false;
+        for (column, c) in line.chars().enumerate() {
+            match c {
+                '/' if !escaped => return column + 2,
+                '\\' => escaped = !escaped,
+                _ => escaped = false,
+            }
         }
+        1
+    }
+
+    fn parse_entry(line: &str) -> Result<(String, Vec<String>, Vec<String>)> {
+        let (word, flags_str, morph) = Self::split_entry(line, false);
+        let flags = flags_str.map(Self::parse_flags).unwrap_or_default();
+        Ok((word, flags, morph))
+    }
+
+    fn parse_entry_with_affix_file(line: &str, affix_file: &AffixFile) -> Result<(String, Vec<String>, Vec<String>)> {
+        let (word, flags_str, morph) = Self::split_entry(line, !affix_file.morph_aliases.is_empty());
+
+        // With an AM table a lone numeric field is a morphological alias
+        if let [alias] = morph.as_slice() {
+            let is_alias = !affix_file.morph_aliases.is_empty() && alias.chars().all(|c| c.is_ascii_digit());
+            if is_alias && !affix_file.morph_aliases.contains_key(alias) {
+                return Err(RunmunchError::InvalidDictionary(format!(
+                    "AM alias {} out of range (1-{})", alias, affix_file.morph_aliases.len()
+                )));
+            }
+        }
+
+        let Some(flags_str) = flags_str else {
+            return Ok((word, Vec::new(), morph));
+        };
+        // With an AF table the flag field is an alias number, resolved here once and for all
+        let flags = affix_file.split_flags(flags_str)?;
+        Ok((word, flags, morph))
+    }
+
+    /// Splits an entry line into the word, its flag string and its morphological fields.
+    ///
+    /// Fields follow a tab, or a space when they look like `xx:` fields (or an alias number, when
+    /// `morph_aliases` is set); other spaces belong to the word. Flags follow the first `/` that is
+    /// not escaped as `\/`.
+    fn split_entry(line: &str, morph_aliases: bool) -> (String, Option<&str>, Vec<String>) {
+        let (head, fields) = match line.split_once('\t') {
+            Some((head, fields)) => (head.trim_end(), fields),
+            None => {
+                let split = line.match_indices(' ')
+                    .map(|(index, _)| index)
+                    .find(|&index| Self::is_morph_field(line[index..].trim_start(), morph_aliases));
+                match split {
+                    Some(index) => (line[..index].trim_end(), &line[index..]),
+                    None => (line, ""),
+                }
+            }
+        };
+        let morph = fields.split_whitespace().map(|field| field.to_string()).collect();
+
+        let mut word = String::new();
+        let mut chars = head.char_indices().peekable();
+        while let Some((index, c)) = chars.next() {
+            match c {
+                '\\' if chars.peek().is_some_and(|&(_, next)| next == '/') => {
+                    word.push('/');
+                    chars.next();
+                }
+                '/' => return (word, Some(&head[index + 1..]), morph),
+                c => word.push(c),
+            }
+        }
+        (word, None, morph)
+    }
+
+    /// Whether `text` starts with a morphological field (`po:noun`) or an alias number.
+    fn is_morph_field(text: &str, morph_aliases: bool) -> bool {
+        let field = text.split_whitespace().next().unwrap_or("");
+        let mut chars = field.chars();
+        let is_tagged = matches!((chars.next(), chars.next(), chars.next()), (Some(a), Some(b), Some(':')) if a.is_alphanumeric() && b.is_alphanumeric());
+        is_tagged || (morph_aliases && !field.is_empty() && field.chars().all(|c| c.is_ascii_digit()))
     }
 
     fn parse_flags(flags_str: &str) -> Vec<String> {
@@ -101,7 +239,7 @@ impl Dictionary {
         } else if flags_str.len() <= 2 {
             // Short alphabetic strings are likely single flags (UN, ED, etc.)
             vec![flags_str.to_string()]
-        } else if flags_str.len() % 2 == 0 && flags_str.chars().all(|c| c.is_ascii_uppercase()) {
+        } else if flags_str.len().is_multiple_of(2) && flags_str.chars().all(|c| c.is_ascii_uppercase()) {
             // Longer even-length uppercase strings might be long flags (pairs)
             flags_str.chars()
                 .collect::<Vec<_>>()
@@ -118,17 +256,189 @@ impl Dictionary {
         self.entries.iter().map(|entry| (&entry.word, &entry.flags))
     }
 
+    pub fn iter(&self) -> impl Iterator<Item = &DictionaryEntry> {
+        self.entries.iter()
+    }
+
+    /// The first entry of `word`; see `get_entries` for homonyms.
     pub fn get_entry(&self, word: &str) -> Option<&DictionaryEntry> {
-        self.word_to_entry.get(word).map(|&idx| &self.entries[idx])
+        self.get_entries(word).next()
+    }
+
+    /// Every entry of `word`, in dictionary order. Homonyms are separate entries with their own
+    /// flags and morphological fields.
+    pub fn get_entries(&self, word: &str) -> impl Iterator<Item = &DictionaryEntry> {
+        self.word_to_entries.get(word)
+            .into_iter()
+            .flatten()
+            .map(|&idx| &self.entries[idx])
     }
 
     pub fn len(&self) -> usize {
         self.entries.len()
     }
 
+    /// Problems found while parsing the dictionary.
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+
     pub fn is_empty(&self) -> bool {
         self.entries.is_empty()
     }
+
+    /// Adds an entry; an entry for a word already present becomes a homonym.
+    pub fn insert(&mut self, entry: DictionaryEntry) {
+        self.word_to_entries.entry(entry.word.clone()).or_default().push(self.entries.len());
+        self.entries.push(entry);
+    }
+
+    /// Removes every entry of `word`, returning them.
+    pub fn remove(&mut self, word: &str) -> Vec<DictionaryEntry> {
+        let (removed, kept) = std::mem::take(&mut self.entries)
+            .into_iter()
+            .partition(|entry| entry.word == word);
+        self.entries = kept;
+        self.rebuild_index();
+        removed
+    }
+
+    /// Applies `update` to every entry of `word`, returning how many entries were updated. The
+    /// update may also rename the entry.
+    pub fn update<F: FnMut(&mut DictionaryEntry)>(&mut self, word: &str, mut update: F) -> usize {
+        let indices = self.word_to_entries.get(word).cloned().unwrap_or_default();
+        for &index in &indices {
+            update(&mut self.entries[index]);
+        }
+        self.rebuild_index();
+        indices.len()
+    }
+
+    /// Adds `flags` to every entry of `word`, skipping flags it already has.
+    pub fn add_flags(&mut self, word: &str, flags: &[String]) -> usize {
+        self.update(word, |entry| {
+            for flag in flags {
+                if !entry.flags.contains(flag) {
+                    entry.flags.push(flag.clone());
+                }
+            }
+        })
+    }
+
+    /// Removes `flags` from every entry of `word`.
+    pub fn remove_flags(&mut self, word: &str, flags: &[String]) -> usize {
+        self.update(word, |entry| {
+            entry.flags.retain(|flag| !flags.contains(flag));
+        })
+    }
+
+    /// Removes entries that repeat an earlier entry's word, flags (in any order) and
+    /// morphological fields, returning how many were removed.
+    pub fn dedup(&mut self) -> usize {
+        let before = self.entries.len();
+        let mut seen = std::collections::HashSet::new();
+        self.entries.retain(|entry| {
+            let mut flags = entry.flags.clone();
+            flags.sort();
+            seen.insert((entry.word.clone(), flags, entry.morph.clone()))
+        });
+        self.rebuild_index();
+        before - self.entries.len()
+    }
+
+    /// Sorts the entries by word; homonyms keep their relative order.
+    pub fn sort(&mut self) {
+        self.entries.sort_by(|a, b| a.word.cmp(&b.word));
+        self.rebuild_index();
+    }
+
+    fn rebuild_index(&mut self) {
+        self.word_to_entries.clear();
+        for (index, entry) in self.entries.iter().enumerate() {
+            self.word_to_entries.entry(entry.word.clone()).or_default().push(index);
+        }
+    }
+
+    /// Writes the dictionary in hunspell's `.dic` format, in the character set of the affix
+    /// file's `SET` directive.
+    pub fn save<P: AsRef<Path>>(&self, path: P, affix_file: Option<&AffixFile>) -> Result<()> {
+        let content = self.serialize(affix_file)?;
+        let bytes = match affix_file.and_then(|affix_file| affix_file.encoding.as_deref()) {
+            Some(charset) => encoding::encode(&content, charset)?,
+            None => content.into_bytes(),
+        };
+        fs::write(path, bytes)?;
+        Ok(())
+    }
+
+    /// `save` for dictionaries whose flag sets may lack an `AF` alias, such as edited or munched
+    /// ones: see `serialize_with_aliases`. Save `affix_file` too.
+    pub fn save_with_aliases<P: AsRef<Path>>(&self, path: P, affix_file: &mut AffixFile) -> Result<()> {
+        self.add_flag_aliases(affix_file);
+        self.save(path, Some(affix_file))
+    }
+
+    /// Serializes the dictionary like `serialize`, first adding an `AF` alias to `affix_file` for
+    /// every flag set that has none. The affix file must then be saved as well, or the new alias
+    /// numbers will not resolve.
+    pub fn serialize_with_aliases(&self, affix_file: &mut AffixFile) -> Result<String> {
+        self.add_flag_aliases(affix_file);
+        self.serialize(Some(affix_file))
+    }
+
+    fn add_flag_aliases(&self, affix_file: &mut AffixFile) {
+        if affix_file.flag_aliases.is_empty() {
+            return;
+        }
+        for entry in self.entries.iter().filter(|entry| !entry.flags.is_empty()) {
+            if Self::encode_flags(&entry.flags, Some(affix_file)).is_err() {
+                affix_file.add_flag_alias(&entry.flags);
+            }
+        }
+    }
+
+    /// Serializes the dictionary in hunspell's `.dic` format: the entry count followed by one
+    /// `word/flags` line per entry, with morphological fields after a tab.
+    ///
+    /// Flags are encoded according to the affix file's `FLAG` type, or as an `AF` alias number
+    /// when it uses aliases; flags that cannot be encoded (including flag sets without an alias,
+    /// see `serialize_with_aliases`) are reported as `RunmunchError::InvalidFlag`. Without an
+    /// affix file flags are written one after another.
+    pub fn serialize(&self, affix_file: Option<&AffixFile>) -> Result<String> {
+        let mut content = format!("{}\n", self.entries.len());
+
+        for entry in &self.entries {
+            content.push_str(&entry.word.replace('/', "\\/"));
+            if !entry.flags.is_empty() {
+                content.push('/');
+                content.push_str(&Self::encode_flags(&entry.flags, affix_file)?);
+            }
+            if !entry.morph.is_empty() {
+                content.push('\t');
+                content.push_str(&entry.morph.join(" "));
+            }
+            content.push('\n');
+        }
+
+        Ok(content)
+    }
+
+    fn encode_flags(flags: &[String], affix_file: Option<&AffixFile>) -> Result<String> {
+        let Some(affix_file) = affix_file else {
+            return Ok(flags.concat());
+        };
+
+        if !affix_file.flag_aliases.is_empty() {
+            return affix_file.find_flag_alias(flags)
+                .ok_or_else(|| RunmunchError::InvalidFlag(format!("No AF alias for flags {:?}", flags)));
+        }
+
+        let encoded = affix_file.flag_type.join(flags);
+        if affix_file.flag_type.split(&encoded).ok().as_deref() != Some(flags) {
+            return Err(RunmunchError::InvalidFlag(format!("Flags {:?} cannot be written as {:?} flags", flags, affix_file.flag_type)));
+        }
+        Ok(encoded)
+    }
 }
 
 impl Default for Dictionary {
@@ -140,6 +450,7 @@ impl Default for Dictionary {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::affix::FlagType;
 
     #[test]
     fn test_parse_flags() {
@@ -153,14 +464,172 @@ mod tests {
         assert_eq!(Dictionary::parse_flags("ED"), vec!["ED"]); // Short strings are treated as single flags
     }
 
+    #[test]
+    fn test_parse_flags_with_flag_type() {
+        let mut affix_file = AffixFile::new();
+        let parse = |affix_file: &AffixFile, line: &str| {
+            Dictionary::parse_entry_with_affix_file(line, affix_file).map(|(_, flags, _)| flags)
+        };
+
+        assert_eq!(parse(&affix_file, "test/AB").unwrap(), vec!["A", "B"]);
+
+        affix_file.flag_type = FlagType::Long;
+        assert_eq!(parse(&affix_file, "test/AaBb").unwrap(), vec!["Aa", "Bb"]);
+        assert!(matches!(parse(&affix_file, "test/ABC"), Err(RunmunchError::InvalidFlag(_))));
+
+        affix_file.flag_type = FlagType::Numeric;
+        assert_eq!(parse(&affix_file, "test/1,23,999").unwrap(), vec!["1", "23", "999"]);
+        assert!(parse(&affix_file, "test/1,,2").is_err());
+        assert!(parse(&affix_file, "test/70000").is_err());
+
+        affix_file.flag_type = FlagType::Utf8;
+        assert_eq!(parse(&affix_file, "test/ÖŁ").unwrap(), vec!["Ö", "Ł"]);
+    }
+
+    #[test]
+    fn test_invalid_flag_reports_line() {
+        let mut affix_file = AffixFile::new();
+        affix_file.flag_type = FlagType::Long;
+
+        let error = Dictionary::parse_with_affix_file("2\nfoo/AB\nbar/ABC\n", &affix_file).unwrap_err();
+        assert_eq!(error.to_string(), "Invalid flag: line 3, column 5: Odd number of characters in long flags: ABC");
+    }
+
+    #[test]
+    fn test_alias_references_are_validated() {
+        let affix_file = AffixFile::parse("AF 2\nAF AB\nAF C\nAM 1\nAM po:noun\n").unwrap();
+
+        let dictionary = Dictionary::parse_with_affix_file("2\nfoo/2 1\nbar/1\n", &affix_file).unwrap();
+        assert_eq!(dictionary.get_entry("foo").unwrap().flags, vec!["C"]);
+
+        let error = Dictionary::parse_with_affix_file("2\nfoo/1\nbar/3\n", &affix_file).unwrap_err();
+        assert_eq!(error.to_string(), "Invalid flag: line 3, column 5: AF alias 3 out of range (1-2)");
+        assert!(Dictionary::parse_with_affix_file("1\nfoo/AB\n", &affix_file).is_err());
+        assert!(Dictionary::parse_with_affix_file("1\nfoo/1 2\n", &affix_file).is_err());
+    }
+
+    #[test]
+    fn test_homonyms() {
+        let dictionary = Dictionary::parse("3\nbank/a\tpo:noun\nbank/b po:verb\nriver\n").unwrap();
+        assert_eq!(dictionary.len(), 3);
+        assert_eq!(dictionary.get_entry("bank").unwrap().flags, vec!["a"]);
+
+        let entries: Vec<_> = dictionary.get_entries("bank").collect();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[1].flags, vec!["b"]);
+        assert_eq!(entries[1].morph, vec!["po:verb"]);
+        assert_eq!(dictionary.get_entries("lake").count(), 0);
+    }
+
+    #[test]
+    fn test_edit_dictionary() {
+        let mut dictionary = Dictionary::parse("3\nwalk/a\ntalk/b\nwalk/a\n").unwrap();
+        let flags = |list: &[&str]| list.iter().map(|flag| flag.to_string()).collect::<Vec<_>>();
+
+        assert_eq!(dictionary.dedup(), 1);
+        assert_eq!(dictionary.add_flags("walk", &flags(&["b", "a"])), 1);
+        assert_eq!(dictionary.get_entry("walk").unwrap().flags, vec!["a", "b"]);
+        assert_eq!(dictionary.remove_flags("walk", &flags(&["a"])), 1);
+        assert_eq!(dictionary.get_entry("walk").unwrap().flags, vec!["b"]);
+
+        dictionary.insert(DictionaryEntry { word: "bark".to_string(), flags: flags(&["c"]), morph: Vec::new() });
+        dictionary.update("talk", |entry| entry.word = "chalk".to_string());
+        assert!(dictionary.get_entry("talk").is_none());
+        dictionary.sort();
+        let words: Vec<&str> = dictionary.iter().map(|entry| entry.word.as_str()).collect();
+        assert_eq!(words, vec!["bark", "chalk", "walk"]);
+
+        assert_eq!(dictionary.remove("chalk").len(), 1);
+        assert_eq!(dictionary.serialize(None).unwrap(), "2\nbark/c\nwalk/b\n");
+    }
+
+    #[test]
+    fn test_edit_aliased_dictionary() {
+        let mut affix_file = AffixFile::parse("FLAG long\nAF 2\nAF AaBb\nAF Cc\n").unwrap();
+        let mut dictionary = Dictionary::parse_with_affix_file("2\nwork/1\ntalk/2\n", &affix_file).unwrap();
+        assert_eq!(dictionary.get_entry("work").unwrap().flags, vec!["Aa", "Bb"]);
+
+        assert_eq!(dictionary.add_flags("work", &["Cc".to_string()]), 1);
+        assert_eq!(dictionary.get_entry("work").unwrap().flags, vec!["Aa", "Bb", "Cc"]);
+        assert!(matches!(dictionary.serialize(Some(&affix_file)), Err(RunmunchError::InvalidFlag(_))));
+
+        assert_eq!(dictionary.serialize_with_aliases(&mut affix_file).unwrap(), "2\nwork/3\ntalk/2\n");
+        assert_eq!(affix_file.flag_aliases["3"], vec!["Aa", "Bb", "Cc"]);
+        assert!(affix_file.serialize().contains("AF 3\nAF AaBb\nAF Cc\nAF AaBbCc\n"));
+
+        dictionary.remove_flags("work", &["Cc".to_string()]);
+        assert_eq!(dictionary.serialize(Some(&affix_file)).unwrap(), "2\nwork/1\ntalk/2\n");
+    }
+
+    #[test]
+    fn test_edit_numeric_aliased_dictionary() {
+        let mut affix_file = AffixFile::parse("FLAG num\nAF 2\nAF 2\nAF 1,2\n").unwrap();
+        let mut dictionary = Dictionary::parse_with_affix_file("1\ncat/1\n", &affix_file).unwrap();
+        assert_eq!(dictionary.get_entry("cat").unwrap().flags, vec!["2"]);
+
+        dictionary.add_flags("cat", &["3".to_string()]);
+        dictionary.add_flags("cat", &["4".to_string()]);
+        assert_eq!(dictionary.get_entry("cat").unwrap().flags, vec!["2", "3", "4"]);
+        assert_eq!(dictionary.serialize_with_aliases(&mut affix_file).unwrap(), "1\ncat/3\n");
+        assert_eq!(affix_file.flag_aliases["3"], vec!["2", "3", "4"]);
+
+        // A real flag that looks like an alias number is still written as its alias
+        dictionary.insert(DictionaryEntry { word: "dog".to_string(), flags: vec!["2".to_string()], morph: Vec::new() });
+        dictionary.remove_flags("cat", &["3".to_string(), "4".to_string()]);
+        let content = dictionary.serialize(Some(&affix_file)).unwrap();
+        assert_eq!(content, "2\ncat/1\ndog/1\n");
+        let reparsed = Dictionary::parse_with_affix_file(&content, &affix_file).unwrap();
+        assert_eq!(reparsed.get_entry("dog").unwrap().flags, vec!["2"]);
+    }
+
+    #[test]
+    fn test_serialize_flags() {
+        let mut affix_file = AffixFile::new();
+        affix_file.flag_type = FlagType::Numeric;
+        let mut dictionary = Dictionary::new();
+        dictionary.insert(DictionaryEntry {
+            word: "km/h".to_string(),
+            flags: vec!["12".to_string(), "3".to_string()],
+            morph: vec!["po:noun".to_string()],
+        });
+        assert_eq!(dictionary.serialize(Some(&affix_file)).unwrap(), "1\nkm\\/h/12,3\tpo:noun\n");
+
+        affix_file.flag_type = FlagType::Long;
+        assert!(matches!(dictionary.serialize(Some(&affix_file)), Err(RunmunchError::InvalidFlag(_))));
+    }
+
     #[test]
     fn test_parse_entry() {
-        let (word, flags) = Dictionary::parse_entry("test/abc").unwrap();
+        let (word, flags, morph) = Dictionary::parse_entry("test/abc").unwrap();
         assert_eq!(word, "test");
         assert_eq!(flags, vec!["a", "b", "c"]);
+        assert!(morph.is_empty());
 
-        let (word, flags) = Dictionary::parse_entry("simple").unwrap();
+        let (word, flags, _) = Dictionary::parse_entry("simple").unwrap();
         assert_eq!(word, "simple");
         assert!(flags.is_empty());
+
+        let (word, flags, morph) = Dictionary::parse_entry("dog/abc\tpo:noun is:sg").unwrap();
+        assert_eq!(word, "dog");
+        assert_eq!(flags, vec!["a", "b", "c"]);
+        assert_eq!(morph, vec!["po:noun", "is:sg"]);
+
+        let (word, flags, _) = Dictionary::parse_entry("km\\/h/abc").unwrap();
+        assert_eq!(word, "km/h");
+        assert_eq!(flags, vec!["a", "b", "c"]);
+
+        let (word, flags, morph) = Dictionary::parse_entry("New York/abc po:name").unwrap();
+        assert_eq!(word, "New York");
+        assert_eq!(flags, vec!["a", "b", "c"]);
+        assert_eq!(morph, vec!["po:name"]);
+
+        let (word, _, morph) = Dictionary::parse_entry("Rio de Janeiro\tpo:name").unwrap();
+        assert_eq!(word, "Rio de Janeiro");
+        assert_eq!(morph, vec!["po:name"]);
+
+        // Without an AM table a numeric field belongs to the word
+        let dictionary = Dictionary::parse("1\nBoeing 747\n").unwrap();
+        let entry = dictionary.get_entry("Boeing 747").unwrap();
+        assert!(entry.morph.is_empty());
     }
 }
\ No newline at end of file
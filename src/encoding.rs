@@ -0,0 +1,93 @@
+use crate::error::{Result, RunmunchError};
+use encoding_rs::{Encoding, UTF_8};
+
+const UTF8_BOM: &[u8] = b"\xEF\xBB\xBF";
+
+/// Looks up the encoding named by an affix file `SET` line.
+///
+/// Hunspell uses names such as `ISO8859-2`, `KOI8-R` or `microsoft-cp1251`, which are mapped
+/// onto the WHATWG labels understood by `encoding_rs`.
+pub fn lookup(charset: &str) -> Result<&'static Encoding> {
+    let normalized = charset.trim().to_ascii_lowercase();
+    let label = match normalized.as_str() {
+        "tis620-2533" => "tis-620",
+        other => other.strip_prefix("microsoft-").unwrap_or(other),
+    };
+
+    Encoding::for_label(label.as_bytes())
+        .ok_or_else(|| RunmunchError::Encoding(format!("Unsupported character set: {}", charset)))
+}
+
+/// Finds the charset declared by a `SET` line in raw affix file bytes.
+pub fn detect_charset(bytes: &[u8]) -> Option<String> {
+    let bytes = bytes.strip_prefix(UTF8_BOM).unwrap_or(bytes);
+
+    bytes.split(|&b| b == b'\n').find_map(|line| {
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        let line = std::str::from_utf8(line).ok()?.trim();
+        let mut parts = line.split_whitespace();
+        match (parts.next(), parts.next()) {
+            (Some("SET"), Some(charset)) => Some(charset.to_string()),
+            _ => None,
+        }
+    })
+}
+
+/// Decodes file contents in the given charset (UTF-8 when `None`), dropping a leading BOM.
+pub fn decode(bytes: &[u8], charset: Option<&str>) -> Result<String> {
+    let encoding = match charset {
+        Some(charset) => lookup(charset)?,
+        None => UTF_8,
+    };
+    let bytes = if encoding == UTF_8 {
+        bytes.strip_prefix(UTF8_BOM).unwrap_or(bytes)
+    } else {
+        bytes
+    };
+
+    encoding
+        .decode_without_bom_handling_and_without_replacement(bytes)
+        .map(|text| text.into_owned())
+        .ok_or_else(|| RunmunchError::Encoding(format!("Input is not valid {}", encoding.name())))
+}
+
+/// Encodes text into the given charset, failing on characters the charset cannot represent.
+pub fn encode(text: &str, charset: &str) -> Result<Vec<u8>> {
+    let encoding = lookup(charset)?;
+    let (bytes, _, had_errors) = encoding.encode(text);
+    if had_errors {
+        return Err(RunmunchError::Encoding(format!("Cannot represent {:?} in {}", text, encoding.name())));
+    }
+    Ok(bytes.into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lookup_hunspell_names() {
+        assert_eq!(lookup("ISO8859-2").unwrap().name(), "ISO-8859-2");
+        assert_eq!(lookup("KOI8-R").unwrap().name(), "KOI8-R");
+        assert_eq!(lookup("microsoft-cp1251").unwrap().name(), "windows-1251");
+        assert_eq!(lookup("UTF-8").unwrap().name(), "UTF-8");
+        assert!(lookup("EBCDIC-42").is_err());
+    }
+
+    #[test]
+    fn test_detect_charset() {
+        assert_eq!(detect_charset(b"\xEF\xBB\xBFSET ISO8859-2\r\nTRY abc\r\n"), Some("ISO8859-2".to_string()));
+        assert_eq!(detect_charset(b"# no charset\nSFX A Y 1\n"), None);
+    }
+
+    #[test]
+    fn test_decode_and_encode_round_trip() {
+        let text = decode(b"ku\xE6a", Some("ISO8859-2")).unwrap();
+        assert_eq!(text, "kuća");
+        assert_eq!(encode(&text, "ISO8859-2").unwrap(), b"ku\xE6a");
+
+        assert_eq!(decode(b"\xEF\xBB\xBF3\n", None).unwrap(), "3\n");
+        assert!(decode(b"ku\xE6a", None).is_err());
+        assert!(encode("ć", "ISO8859-1").is_err());
+    }
+}
@@ -20,11 +20,11 @@ pub enum RunmunchError {
     #[error("No dictionary loaded")]
     NoDictionary,
     
-    #[error("Regex error: {0}")]
-    Regex(#[from] regex::Error),
-    
     #[error("Invalid flag: {0}")]
     InvalidFlag(String),
+    
+    #[error("Encoding error: {0}")]
+    Encoding(String),
 }
 
 pub type Result<T> = std::result::Result<T, RunmunchError>;
\ No newline at end of file
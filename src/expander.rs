@@ -1,6 +1,279 @@
-use crate::affix::{AffixFile, AffixType};
+use crate::affix::{AffixFile, AffixRule, AffixType, WordStatus};
+use crate::compound::{CompoundLimits, CompoundPosition, CompoundRule, Quantifier};
+use crate::dictionary::DictionaryEntry;
 use crate::error::{Result, RunmunchError};
-use std::collections::{HashSet, VecDeque};
+use crate::suggest::{ngram_similarity, MAX_NGRAM_SUGGESTIONS, MAX_SUGGESTIONS};
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+
+/// Upper bound on the number of derivations generated for a single root.
+const MAX_DERIVATIONS: usize = 10000;
+
+/// A generated surface form with the morphological fields accumulated from its root entry and
+/// every affix rule applied to it.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ExpandedForm {
+    pub word: String,
+    pub morph: Vec<String>,
+    pub status: WordStatus,
+}
+
+/// An affix rule applied in a derivation, identified by its flag and its position within the
+/// rules of that flag (counting from 0, in affix file order).
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct AppliedRule {
+    pub affix_type: AffixType,
+    pub flag: String,
+    pub index: usize,
+}
+
+impl AppliedRule {
+    fn new(affix_file: &AffixFile, rule: &AffixRule, affix_type: AffixType) -> Self {
+        let rules = match affix_type {
+            AffixType::Prefix => affix_file.get_prefix_rules(&rule.flag),
+            AffixType::Suffix => affix_file.get_suffix_rules(&rule.flag),
+        };
+        let index = rules.into_iter()
+            .flatten()
+            .position(|candidate| std::ptr::eq(candidate, rule))
+            .unwrap_or_default();
+        AppliedRule {
+            affix_type,
+            flag: rule.flag.clone(),
+            index,
+        }
+    }
+}
+
+impl std::fmt::Display for AppliedRule {
+    /// `SFX:flag:index` or `PFX:flag:index`.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let kind = match self.affix_type {
+            AffixType::Prefix => "PFX",
+            AffixType::Suffix => "SFX",
+        };
+        write!(f, "{}:{}:{}", kind, self.flag, self.index)
+    }
+}
+
+/// A generated form with its provenance: the dictionary entry it was generated from and the
+/// rules applied to it, in the order they were applied (suffixes innermost first, then
+/// prefixes).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DerivedForm {
+    pub word: String,
+    pub entry: DictionaryEntry,
+    pub rules: Vec<AppliedRule>,
+    /// Whether both prefixes and suffixes were applied, combined as a cross product.
+    pub cross_product: bool,
+}
+
+/// One morphological analysis of a word.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Analysis {
+    /// The dictionary entry the word derives from.
+    pub entry: DictionaryEntry,
+    /// Prefix rules applied, innermost first.
+    pub prefixes: Vec<AppliedRule>,
+    /// Suffix rules applied, innermost first.
+    pub suffixes: Vec<AppliedRule>,
+    /// Fields of the entry (with `st:` set to the entry's word unless it has its own), then
+    /// those of the prefix and suffix rules in the order they were applied. `AM` aliases are
+    /// resolved.
+    pub morph: Vec<String>,
+}
+
+/// A surface form together with the rules that produced it from its root, innermost first.
+#[derive(Debug, Clone)]
+struct Derivation<'a> {
+    word: String,
+    prefixes: Vec<&'a AffixRule>,
+    suffixes: Vec<&'a AffixRule>,
+}
+
+impl Derivation<'_> {
+    /// Root fields first, then prefix and suffix fields in the order the rules were applied.
+    fn morph(&self, root_morph: &[String]) -> Vec<String> {
+        let mut morph = root_morph.to_vec();
+        for rule in self.rules() {
+            morph.extend(rule.morph.iter().cloned());
+        }
+        morph
+    }
+
+    /// Checks the `CIRCUMFIX` and `NEEDAFFIX` constraints hunspell places on a complete
+    /// derivation: circumfix prefixes and suffixes must co-occur, a need-affix root must carry
+    /// at least one affix, and a need-affix rule must be accompanied by another affix.
+    fn is_surface_form(&self, affix_file: &AffixFile, root_flags: &[String]) -> bool {
+        let affix_count = self.prefixes.len() + self.suffixes.len();
+        if affix_count == 0 {
+            return !affix_file.needs_affix(root_flags);
+        }
+        if affix_count == 1 && self.rules().any(|rule| affix_file.needs_affix(&rule.continuation)) {
+            return false;
+        }
+
+        let circumfix_prefix = self.prefixes.iter().any(|rule| affix_file.is_circumfix(&rule.continuation));
+        let circumfix_suffix = self.suffixes.iter().any(|rule| affix_file.is_circumfix(&rule.continuation));
+        circumfix_prefix == circumfix_suffix
+    }
+
+    /// Status flags of the root combined with those in the continuation classes of every rule.
+    fn status(&self, affix_file: &AffixFile, root_flags: &[String]) -> WordStatus {
+        self.rules().fold(affix_file.word_status(root_flags), |status, rule| {
+            status.merge(affix_file.word_status(&rule.continuation))
+        })
+    }
+
+    /// Whether a root carrying `root_flags` licenses the rules of this derivation the way
+    /// `WordExpander::derive` combines them: the innermost suffix and prefix come from the root's
+    /// flags (or from the continuation class of an affix of the other type), suffixes and
+    /// prefixes only combine when all of them allow cross products, and the affix counts stay
+    /// within the affix model. Each further affix must already be named in the continuation
+    /// class of the one inside it.
+    fn is_licensed(&self, affix_file: &AffixFile, root_flags: &[String]) -> bool {
+        if self.prefixes.len() > affix_file.max_affixes(&AffixType::Prefix)
+            || self.suffixes.len() > affix_file.max_affixes(&AffixType::Suffix)
+        {
+            return false;
+        }
+        if !self.prefixes.is_empty() && !self.suffixes.is_empty() && !self.rules().all(|rule| rule.cross_product) {
+            return false;
+        }
+
+        if let Some(inner_prefix) = self.prefixes.first() {
+            let licensed = root_flags.contains(&inner_prefix.flag)
+                || self.suffixes.iter().any(|rule| rule.continuation.contains(&inner_prefix.flag));
+            if !licensed {
+                return false;
+            }
+        }
+        match self.suffixes.first() {
+            Some(inner_suffix) if !root_flags.contains(&inner_suffix.flag) => {
+                let from_root_prefix = root_flags.iter()
+                    .flat_map(|flag| affix_file.get_prefix_rules(flag).into_iter().flatten())
+                    .any(|rule| rule.continuation.contains(&inner_suffix.flag));
+                from_root_prefix && self.prefixes.iter().any(|rule| rule.continuation.contains(&inner_suffix.flag))
+            }
+            _ => true,
+        }
+    }
+
+    fn rules(&self) -> impl Iterator<Item = &AffixRule> {
+        self.prefixes.iter().chain(self.suffixes.iter()).copied()
+    }
+
+    /// The derived form with the rules in the order they were applied.
+    fn derived_form(&self, affix_file: &AffixFile, entry: &DictionaryEntry) -> DerivedForm {
+        let suffixes = self.suffixes.iter().map(|rule| AppliedRule::new(affix_file, rule, AffixType::Suffix));
+        let prefixes = self.prefixes.iter().map(|rule| AppliedRule::new(affix_file, rule, AffixType::Prefix));
+        DerivedForm {
+            word: self.word.clone(),
+            entry: entry.clone(),
+            rules: suffixes.chain(prefixes).collect(),
+            cross_product: !self.prefixes.is_empty() && !self.suffixes.is_empty(),
+        }
+    }
+}
+
+/// The spellings of `word` a dictionary entry may have: the word itself, and for a capitalized
+/// word its lowercase form, or for an all-uppercase word its capitalized and lowercase forms.
+fn case_variants(word: &str) -> Vec<String> {
+    let lower = word.to_lowercase();
+    let mut chars = lower.chars();
+    let capitalized: String = chars.next()
+        .map(|first| first.to_uppercase().chain(chars).collect())
+        .unwrap_or_default();
+
+    let mut variants = vec![word.to_string()];
+    if word != lower {
+        if word == word.to_uppercase() && capitalized != word {
+            variants.push(capitalized);
+            variants.push(lower);
+        } else if word == capitalized {
+            variants.push(lower);
+        }
+    }
+    variants
+}
+
+/// Candidate compound parts, each with its length in characters, sorted shortest first.
+struct CompoundBuilder {
+    middles: Vec<(String, usize)>,
+    ends: Vec<(String, usize)>,
+    max_parts: usize,
+    max_length: usize,
+}
+
+impl CompoundBuilder {
+    /// Closes `current` (made of `parts` parts) with every end part that fits, then recurses
+    /// through the middle parts while more parts are allowed.
+    fn extend(&self, current: &str, length: usize, parts: usize, compounds: &mut HashSet<String>) {
+        for (end, end_length) in &self.ends {
+            if length + end_length > self.max_length {
+                break;
+            }
+            compounds.insert(format!("{}{}", current, end));
+        }
+
+        if parts + 2 > self.max_parts {
+            return;
+        }
+        for (middle, middle_length) in &self.middles {
+            if length + middle_length > self.max_length {
+                break;
+            }
+            let extended = format!("{}{}", current, middle);
+            self.extend(&extended, length + middle_length, parts + 1, compounds);
+        }
+    }
+}
+
+/// Dictionary words usable in `COMPOUNDRULE` compounds, grouped by flag and sorted shortest first.
+struct CompoundRuleBuilder<'a> {
+    parts_by_flag: HashMap<String, Vec<(&'a str, usize)>>,
+    max_parts: usize,
+    max_length: usize,
+    heads: Option<&'a HashSet<String>>,
+}
+
+impl<'a> CompoundRuleBuilder<'a> {
+    /// Extends the parts chosen so far with words matching the pattern from `element` on.
+    fn extend(&self, rule: &CompoundRule, element: usize, current: &mut Vec<&'a str>, length: usize, compounds: &mut HashSet<String>) {
+        let Some((flag, quantifier)) = rule.elements.get(element) else {
+            let head_allowed = self.heads.is_none_or(|heads| current.last().is_some_and(|head| heads.contains(*head)));
+            if current.len() >= 2 && head_allowed {
+                compounds.insert(current.concat());
+            }
+            return;
+        };
+
+        if *quantifier != Quantifier::One {
+            self.extend(rule, element + 1, current, length, compounds);
+        }
+        if current.len() >= self.max_parts {
+            return;
+        }
+
+        let next = if *quantifier == Quantifier::ZeroOrMore { element } else { element + 1 };
+        for &(word, word_length) in self.parts_by_flag.get(flag).into_iter().flatten() {
+            if length + word_length > self.max_length {
+                break;
+            }
+            current.push(word);
+            self.extend(rule, next, current, length + word_length, compounds);
+            current.pop();
+        }
+    }
+}
+
+fn push_unique(flags: &mut Vec<String>, extra: &[String]) {
+    for flag in extra {
+        if !flags.contains(flag) {
+            flags.push(flag.clone());
+        }
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct WordExpander {
@@ -23,15 +296,18 @@ impl WordExpander {
 
     pub fn expand_with_all_rules(&self, word: &str) -> Result<Vec<String>> {
         let affix_file = self.affix_file.as_ref()
-            .ok_or_else(|| RunmunchError::NoAffixFile)?;
+            .ok_or(RunmunchError::NoAffixFile)?;
 
         let mut results = HashSet::new();
         results.insert(word.to_string());
 
-        // Try all prefix rules
-        for (_flag, rules) in &affix_file.prefixes {
+        // Try all prefix rules; circumfix and need-affix rules never form a word on their own
+        for rules in affix_file.prefixes.values() {
             for rule in rules {
-                if rule.can_apply(word, &AffixType::Prefix) {
+                if affix_file.is_circumfix(&rule.continuation) || affix_file.needs_affix(&rule.continuation) {
+                    continue;
+                }
+                if affix_file.rule_applies(rule, word, &AffixType::Prefix) {
                     let expanded = rule.apply(word, &AffixType::Prefix);
                     results.insert(expanded);
                 }
@@ -39,9 +315,12 @@ impl WordExpander {
         }
 
         // Try all suffix rules
-        for (_flag, rules) in &affix_file.suffixes {
+        for rules in affix_file.suffixes.values() {
             for rule in rules {
-                if rule.can_apply(word, &AffixType::Suffix) {
+                if affix_file.is_circumfix(&rule.continuation) || affix_file.needs_affix(&rule.continuation) {
+                    continue;
+                }
+                if affix_file.rule_applies(rule, word, &AffixType::Suffix) {
                     let expanded = rule.apply(word, &AffixType::Suffix);
                     results.insert(expanded);
                 }
@@ -53,71 +332,482 @@ impl WordExpander {
         Ok(sorted_results)
     }
 
+    /// Generates the words formed from `word` with `flags`. Flags are used as given: `AF` alias
+    /// numbers are resolved when the dictionary is parsed, not here.
     pub fn expand_with_flags(&self, word: &str, flags: &[String]) -> Result<Vec<String>> {
         let affix_file = self.affix_file.as_ref()
-            .ok_or_else(|| RunmunchError::NoAffixFile)?;
+            .ok_or(RunmunchError::NoAffixFile)?;
 
-        // Expand flag aliases first
-        let expanded_flags = affix_file.expand_flags(flags);
+        let results: HashSet<String> = Self::derive(affix_file, word, flags)
+            .into_iter()
+            .filter(|derivation| derivation.status(affix_file, flags).is_standalone_word())
+            .map(|derivation| derivation.word)
+            .collect();
 
-        let mut results = HashSet::new();
-        let mut queue = VecDeque::new();
-        let mut iterations = 0;
-        const MAX_ITERATIONS: usize = 10000;
+        let mut sorted_results: Vec<String> = results.into_iter().collect();
+        sorted_results.sort();
+        Ok(sorted_results)
+    }
 
-        results.insert(word.to_string());
-        queue.push_back((word.to_string(), expanded_flags, false, 0));
+    /// Like `expand_with_flags` for a dictionary entry, but records for each form the rules that
+    /// produced it. A form reachable in several ways appears once per derivation.
+    pub fn expand_with_provenance(&self, entry: &DictionaryEntry) -> Result<Vec<DerivedForm>> {
+        let affix_file = self.affix_file.as_ref()
+            .ok_or(RunmunchError::NoAffixFile)?;
 
-        while let Some((current_word, current_flags, has_suffix, depth)) = queue.pop_front() {
-            iterations += 1;
-            if iterations > MAX_ITERATIONS || depth > 2 {
-                break;
+        let flags = &entry.flags;
+        let mut forms: Vec<DerivedForm> = Vec::new();
+        for derivation in Self::derive(affix_file, &entry.word, flags) {
+            if !derivation.status(affix_file, flags).is_standalone_word() {
+                continue;
             }
-            for flag in &current_flags {
-                if let Some(suffix_rules) = affix_file.get_suffix_rules(flag) {
-                    for rule in suffix_rules {
-                        if rule.can_apply(&current_word, &AffixType::Suffix) {
-                            let expanded = rule.apply(&current_word, &AffixType::Suffix);
-                            if results.insert(expanded.clone()) {
-                                if rule.cross_product && depth < 1 {
-                                    queue.push_back((expanded, current_flags.clone(), true, depth + 1));
-                                }
-                            }
-                        }
+            let form = derivation.derived_form(affix_file, entry);
+            if !forms.contains(&form) {
+                forms.push(form);
+            }
+        }
+
+        forms.sort_by(|a, b| a.word.cmp(&b.word));
+        Ok(forms)
+    }
+
+    /// Like `expand_with_all_rules`, but records the rule behind each form. The entry of every
+    /// form is `word` itself, without flags.
+    pub fn expand_all_with_provenance(&self, word: &str) -> Result<Vec<DerivedForm>> {
+        let affix_file = self.affix_file.as_ref()
+            .ok_or(RunmunchError::NoAffixFile)?;
+
+        let entry = DictionaryEntry {
+            word: word.to_string(),
+            flags: Vec::new(),
+            morph: Vec::new(),
+        };
+        let mut forms = vec![DerivedForm {
+            word: word.to_string(),
+            entry: entry.clone(),
+            rules: Vec::new(),
+            cross_product: false,
+        }];
+
+        for (affix_type, blocks) in [(AffixType::Prefix, &affix_file.prefixes), (AffixType::Suffix, &affix_file.suffixes)] {
+            for rules in blocks.values() {
+                for (index, rule) in rules.iter().enumerate() {
+                    if affix_file.is_circumfix(&rule.continuation) || affix_file.needs_affix(&rule.continuation) {
+                        continue;
+                    }
+                    if affix_file.rule_applies(rule, word, &affix_type) {
+                        forms.push(DerivedForm {
+                            word: rule.apply(word, &affix_type),
+                            entry: entry.clone(),
+                            rules: vec![AppliedRule { affix_type: affix_type.clone(), flag: rule.flag.clone(), index }],
+                            cross_product: false,
+                        });
                     }
                 }
             }
+        }
 
-            if has_suffix {
-                for flag in &current_flags {
-                    if let Some(prefix_rules) = affix_file.get_prefix_rules(flag) {
-                        for rule in prefix_rules {
-                            if rule.cross_product && rule.can_apply(&current_word, &AffixType::Prefix) {
-                                let expanded = rule.apply(&current_word, &AffixType::Prefix);
-                                results.insert(expanded);
-                            }
-                        }
-                    }
+        forms.sort_by(|a, b| a.word.cmp(&b.word).then_with(|| a.rules.iter().map(ToString::to_string).cmp(b.rules.iter().map(ToString::to_string))));
+        Ok(forms)
+    }
+
+    /// Like `expand_with_flags`, but keeps the morphological fields and entry status of each
+    /// generated form. `morph` holds the fields of the root entry; `AM` aliases are resolved on
+    /// both the root and the rules. Forbidden and compound-only forms are left out.
+    pub fn expand_with_morphology(&self, word: &str, flags: &[String], morph: &[String]) -> Result<Vec<ExpandedForm>> {
+        let affix_file = self.affix_file.as_ref()
+            .ok_or(RunmunchError::NoAffixFile)?;
+
+        let root_morph = affix_file.expand_morph(morph);
+
+        let mut seen = HashSet::new();
+        let mut forms = Vec::new();
+        for derivation in Self::derive(affix_file, word, flags) {
+            let status = derivation.status(affix_file, flags);
+            if !status.is_standalone_word() {
+                continue;
+            }
+            let form = ExpandedForm {
+                morph: derivation.morph(&root_morph),
+                status,
+                word: derivation.word,
+            };
+            if seen.insert(form.clone()) {
+                forms.push(form);
+            }
+        }
+
+        forms.sort_by(|a, b| a.word.cmp(&b.word));
+        Ok(forms)
+    }
+
+    /// Collects the surface forms of dictionary entries marked with `FORBIDDENWORD`, including
+    /// their affixed forms. These words must not be output even when another root generates them.
+    pub fn forbidden_forms(&self, dictionary: &crate::Dictionary) -> Result<HashSet<String>> {
+        let affix_file = self.affix_file.as_ref()
+            .ok_or(RunmunchError::NoAffixFile)?;
+
+        let mut forbidden = HashSet::new();
+        for entry in dictionary.iter() {
+            let flags = &entry.flags;
+            if affix_file.word_status(flags).forbidden {
+                forbidden.insert(entry.word.clone());
+                forbidden.extend(Self::derive(affix_file, &entry.word, flags).into_iter().map(|derivation| derivation.word));
+            }
+        }
+
+        Ok(forbidden)
+    }
+
+    /// Generates compounds from the dictionary entries that carry `COMPOUNDFLAG`,
+    /// `COMPOUNDBEGIN`, `COMPOUNDMIDDLE` or `COMPOUNDEND`, within `limits`.
+    ///
+    /// Parts may be affixed forms of their roots. Prefixes are only kept on the first part and
+    /// suffixes on the last one unless the rule carries `COMPOUNDPERMITFLAG`; forms built with a
+    /// `COMPOUNDFORBIDFLAG` rule and parts shorter than `COMPOUNDMIN` are never used.
+    pub fn expand_compounds(&self, dictionary: &crate::Dictionary, limits: &CompoundLimits) -> Result<Vec<String>> {
+        let affix_file = self.affix_file.as_ref()
+            .ok_or(RunmunchError::NoAffixFile)?;
+        let options = &affix_file.compound;
+        if !options.is_enabled() {
+            // Compounds described by COMPOUNDRULE are generated by expand_compound_rules
+            return Ok(Vec::new());
+        }
+        let max_parts = options.word_max.map_or(limits.max_parts, |word_max| word_max.min(limits.max_parts));
+
+        let mut begins = Vec::new();
+        let mut middles = Vec::new();
+        let mut ends = Vec::new();
+
+        for entry in dictionary.iter() {
+            let flags = &entry.flags;
+            for derivation in Self::derive(affix_file, &entry.word, flags) {
+                if derivation.status(affix_file, flags).forbidden
+                    || derivation.rules().any(|rule| options.forbids(&rule.continuation)) {
+                    continue;
                 }
-            } else {
-                for flag in &current_flags {
-                    if let Some(prefix_rules) = affix_file.get_prefix_rules(flag) {
-                        for rule in prefix_rules {
-                            if rule.can_apply(&current_word, &AffixType::Prefix) {
-                                let expanded = rule.apply(&current_word, &AffixType::Prefix);
-                                results.insert(expanded);
-                            }
-                        }
-                    }
+                let length = derivation.word.chars().count();
+                if length < options.min_length {
+                    continue;
+                }
+
+                // Compound flags may also come from the continuation classes of the rules
+                let mut part_flags = flags.clone();
+                for rule in derivation.rules() {
+                    push_unique(&mut part_flags, &rule.continuation);
+                }
+                let inner_suffixes = derivation.suffixes.iter().all(|rule| options.permits(&rule.continuation));
+                let inner_prefixes = derivation.prefixes.iter().all(|rule| options.permits(&rule.continuation));
+                let head_allowed = limits.heads.as_ref().is_none_or(|heads| heads.contains(&entry.word));
+
+                let part = (derivation.word, length);
+                if inner_suffixes && options.allows(CompoundPosition::Begin, &part_flags) {
+                    begins.push(part.clone());
+                }
+                if inner_suffixes && inner_prefixes && options.allows(CompoundPosition::Middle, &part_flags) {
+                    middles.push(part.clone());
+                }
+                if inner_prefixes && head_allowed && options.allows(CompoundPosition::End, &part_flags) {
+                    ends.push(part);
                 }
             }
         }
 
-        let mut sorted_results: Vec<String> = results.into_iter().collect();
+        // Shortest parts first, so that the length bound can stop each scan early
+        for parts in [&mut begins, &mut middles, &mut ends] {
+            parts.sort_by(|a, b| a.1.cmp(&b.1).then_with(|| a.0.cmp(&b.0)));
+            parts.dedup();
+        }
+
+        let builder = CompoundBuilder {
+            middles,
+            ends,
+            max_parts,
+            max_length: limits.max_length,
+        };
+        let mut compounds = HashSet::new();
+        if max_parts >= 2 {
+            for (word, length) in &begins {
+                builder.extend(word, *length, 1, &mut compounds);
+            }
+        }
+
+        let mut sorted_results: Vec<String> = compounds.into_iter().collect();
         sorted_results.sort();
         Ok(sorted_results)
     }
 
+    /// Whether `word` is a compound `expand_compounds` could generate: at least two parts, each a
+    /// form of a dictionary entry allowed at its position by `COMPOUNDFLAG`, `COMPOUNDBEGIN`,
+    /// `COMPOUNDMIDDLE` or `COMPOUNDEND`, under the same affix and `COMPOUNDMIN` constraints.
+    pub fn check_compound(&self, word: &str, dictionary: &crate::Dictionary) -> Result<bool> {
+        let affix_file = self.affix_file.as_ref()
+            .ok_or(RunmunchError::NoAffixFile)?;
+        if !affix_file.compound.is_enabled() {
+            return Ok(false);
+        }
+
+        let chars: Vec<char> = word.chars().collect();
+        Ok(Self::split_compound(affix_file, dictionary, &chars, 0))
+    }
+
+    fn split_compound(affix_file: &AffixFile, dictionary: &crate::Dictionary, rest: &[char], parts: usize) -> bool {
+        let options = &affix_file.compound;
+        if options.word_max.is_some_and(|word_max| parts >= word_max) {
+            return false;
+        }
+
+        for end in options.min_length.max(1)..=rest.len() {
+            let is_last = end == rest.len();
+            if is_last && parts == 0 {
+                break;
+            }
+            let position = match (parts, is_last) {
+                (0, _) => CompoundPosition::Begin,
+                (_, true) => CompoundPosition::End,
+                _ => CompoundPosition::Middle,
+            };
+            let part: String = rest[..end].iter().collect();
+            if Self::is_compound_part(affix_file, dictionary, &part, position)
+                && (is_last || Self::split_compound(affix_file, dictionary, &rest[end..], parts + 1))
+            {
+                return true;
+            }
+        }
+
+        false
+    }
+
+    /// Whether `part` is a form of a dictionary entry that may stand at `position` of a compound.
+    fn is_compound_part(affix_file: &AffixFile, dictionary: &crate::Dictionary, part: &str, position: CompoundPosition) -> bool {
+        let options = &affix_file.compound;
+        for (root, derivation) in Self::reverse_derivations(affix_file, part) {
+            for entry in dictionary.get_entries(&root) {
+                let flags = &entry.flags;
+                if !derivation.is_licensed(affix_file, flags)
+                    || !derivation.is_surface_form(affix_file, flags)
+                    || derivation.status(affix_file, flags).forbidden
+                    || derivation.rules().any(|rule| options.forbids(&rule.continuation))
+                {
+                    continue;
+                }
+
+                let mut part_flags = flags.clone();
+                for rule in derivation.rules() {
+                    push_unique(&mut part_flags, &rule.continuation);
+                }
+                let inner_suffixes = derivation.suffixes.iter().all(|rule| options.permits(&rule.continuation));
+                let inner_prefixes = derivation.prefixes.iter().all(|rule| options.permits(&rule.continuation));
+                let affixes_allowed = match position {
+                    CompoundPosition::Begin => inner_suffixes,
+                    CompoundPosition::Middle => inner_suffixes && inner_prefixes,
+                    CompoundPosition::End => inner_prefixes,
+                };
+                if affixes_allowed && options.allows(position, &part_flags) {
+                    return true;
+                }
+            }
+        }
+        false
+    }
+
+    /// Whether `word` splits into at least two dictionary words whose flags, in order, match one
+    /// of the `COMPOUNDRULE` patterns.
+    pub fn check_compound_rules(&self, word: &str, dictionary: &crate::Dictionary) -> Result<bool> {
+        let affix_file = self.affix_file.as_ref()
+            .ok_or(RunmunchError::NoAffixFile)?;
+        if affix_file.compound.rules.is_empty() {
+            return Ok(false);
+        }
+
+        let chars: Vec<char> = word.chars().collect();
+        Ok(Self::split_by_compound_rules(affix_file, dictionary, &chars, &mut Vec::new()))
+    }
+
+    fn split_by_compound_rules(affix_file: &AffixFile, dictionary: &crate::Dictionary, rest: &[char], parts: &mut Vec<Vec<String>>) -> bool {
+        let options = &affix_file.compound;
+        if rest.is_empty() {
+            let views: Vec<&[String]> = parts.iter().map(|flags| flags.as_slice()).collect();
+            return views.len() >= 2 && options.rules.iter().any(|rule| rule.matches(&views));
+        }
+        if options.word_max.is_some_and(|word_max| parts.len() >= word_max) {
+            return false;
+        }
+
+        for end in options.min_length.max(1)..=rest.len() {
+            let part: String = rest[..end].iter().collect();
+            for entry in dictionary.get_entries(&part) {
+                if affix_file.word_status(&entry.flags).forbidden {
+                    continue;
+                }
+
+                parts.push(entry.flags.clone());
+                if Self::split_by_compound_rules(affix_file, dictionary, &rest[end..], parts) {
+                    return true;
+                }
+                parts.pop();
+            }
+        }
+
+        false
+    }
+
+    /// Enumerates the compounds described by the `COMPOUNDRULE` patterns, built from dictionary
+    /// words, within `limits`.
+    pub fn expand_compound_rules(&self, dictionary: &crate::Dictionary, limits: &CompoundLimits) -> Result<Vec<String>> {
+        let affix_file = self.affix_file.as_ref()
+            .ok_or(RunmunchError::NoAffixFile)?;
+        let options = &affix_file.compound;
+
+        let mut parts_by_flag: HashMap<String, Vec<(&str, usize)>> = HashMap::new();
+        for flag in options.rules.iter().flat_map(|rule| rule.flags()) {
+            parts_by_flag.entry(flag.clone()).or_default();
+        }
+        for entry in dictionary.iter() {
+            let flags = &entry.flags;
+            let length = entry.word.chars().count();
+            if length < options.min_length || affix_file.word_status(flags).forbidden {
+                continue;
+            }
+            for flag in flags {
+                if let Some(parts) = parts_by_flag.get_mut(flag) {
+                    parts.push((entry.word.as_str(), length));
+                }
+            }
+        }
+        for parts in parts_by_flag.values_mut() {
+            parts.sort_by(|a, b| a.1.cmp(&b.1).then_with(|| a.0.cmp(b.0)));
+            parts.dedup();
+        }
+
+        let builder = CompoundRuleBuilder {
+            parts_by_flag,
+            max_parts: options.word_max.map_or(limits.max_parts, |word_max| word_max.min(limits.max_parts)),
+            max_length: limits.max_length,
+            heads: limits.heads.as_ref(),
+        };
+        let mut compounds = HashSet::new();
+        for rule in &options.rules {
+            builder.extend(rule, 0, &mut Vec::new(), 0, &mut compounds);
+        }
+
+        let mut sorted_results: Vec<String> = compounds.into_iter().collect();
+        sorted_results.sort();
+        Ok(sorted_results)
+    }
+
+    /// Enumerates every way of deriving a surface form from `root` with the given flags,
+    /// including the bare root itself unless it needs an affix.
+    ///
+    /// Suffixes are applied first, then prefixes. A prefix combines with suffixes only when all
+    /// rules involved allow cross products. Continuation flags let a suffix license a second
+    /// suffix or a prefix, and a prefix license a second prefix or a suffix.
+    fn derive<'a>(affix_file: &'a AffixFile, root: &str, flags: &[String]) -> Vec<Derivation<'a>> {
+        let mut derivations = Vec::new();
+
+        // Suffixes licensed only by a prefix's continuation class are enumerated as well and
+        // kept once a matching prefix is found.
+        let mut suffix_flags = flags.to_vec();
+        for flag in flags {
+            if let Some(prefix_rules) = affix_file.get_prefix_rules(flag) {
+                for rule in prefix_rules {
+                    push_unique(&mut suffix_flags, &rule.continuation);
+                }
+            }
+        }
+
+        let suffix_chains = Self::affix_chains(affix_file, root, &suffix_flags, AffixType::Suffix);
+        for (suffixed, suffixes) in suffix_chains {
+            let licensed_by_root = suffixes.first().is_none_or(|rule| flags.contains(&rule.flag));
+            if licensed_by_root {
+                let derivation = Derivation {
+                    word: suffixed.clone(),
+                    prefixes: Vec::new(),
+                    suffixes: suffixes.clone(),
+                };
+                if derivation.is_surface_form(affix_file, flags) {
+                    derivations.push(derivation);
+                }
+            }
+
+            if !suffixes.iter().all(|rule| rule.cross_product) {
+                continue;
+            }
+
+            let mut prefix_flags = flags.to_vec();
+            for rule in &suffixes {
+                push_unique(&mut prefix_flags, &rule.continuation);
+            }
+
+            for (prefixed, prefixes) in Self::affix_chains(affix_file, &suffixed, &prefix_flags, AffixType::Prefix) {
+                if prefixes.is_empty() {
+                    continue;
+                }
+                if !suffixes.is_empty() && !prefixes.iter().all(|rule| rule.cross_product) {
+                    continue;
+                }
+                if !licensed_by_root {
+                    let inner_flag = &suffixes[0].flag;
+                    if !prefixes.iter().any(|rule| rule.continuation.contains(inner_flag)) {
+                        continue;
+                    }
+                }
+                let derivation = Derivation {
+                    word: prefixed,
+                    prefixes,
+                    suffixes: suffixes.clone(),
+                };
+                if derivation.is_surface_form(affix_file, flags) {
+                    derivations.push(derivation);
+                }
+            }
+
+            if derivations.len() >= MAX_DERIVATIONS {
+                break;
+            }
+        }
+
+        derivations
+    }
+
+    /// Enumerates chains of affixes of one type applied to `word`, starting with the empty chain,
+    /// up to the number of affixes of that type the affix model allows.
+    /// The first affix is taken from `flags`; each further affix must be named in the
+    /// continuation class of the one applied before it.
+    fn affix_chains<'a>(affix_file: &'a AffixFile, word: &str, flags: &[String], affix_type: AffixType) -> Vec<(String, Vec<&'a AffixRule>)> {
+        let mut chains: Vec<(String, Vec<&AffixRule>)> = vec![(word.to_string(), Vec::new())];
+        let mut next = 0;
+
+        while next < chains.len() && chains.len() < MAX_DERIVATIONS {
+            let (current_word, chain) = chains[next].clone();
+            next += 1;
+
+            if chain.len() >= affix_file.max_affixes(&affix_type) {
+                continue;
+            }
+            let available_flags: &[String] = match chain.last() {
+                Some(rule) => &rule.continuation,
+                None => flags,
+            };
+
+            for flag in available_flags {
+                let rules = match affix_type {
+                    AffixType::Prefix => affix_file.get_prefix_rules(flag),
+                    AffixType::Suffix => affix_file.get_suffix_rules(flag),
+                };
+                for rule in rules.into_iter().flatten() {
+                    if affix_file.rule_applies(rule, &current_word, &affix_type) {
+                        let mut extended = chain.clone();
+                        extended.push(rule);
+                        chains.push((rule.apply(&current_word, &affix_type), extended));
+                    }
+                }
+            }
+        }
+
+        chains
+    }
+
     pub fn expand_words_from_stdin(&self) -> Result<Vec<String>> {
         use std::io::{self, BufRead, BufReader};
 
@@ -141,91 +831,527 @@ impl WordExpander {
     }
 
     pub fn find_base_word(&self, inflected_word: &str, dictionary: &crate::Dictionary) -> Result<Vec<String>> {
+        let mut base_words = HashSet::new();
+
+        for entry in self.find_base_entries(inflected_word, dictionary)? {
+            base_words.insert(entry.word.clone());
+        }
+
+        let mut sorted_results: Vec<String> = base_words.into_iter().collect();
+        sorted_results.sort();
+        Ok(sorted_results)
+    }
+
+    /// Dictionary entries whose flags generate `inflected_word`; a homonym whose flags do not is
+    /// left out.
+    pub fn find_base_entries<'d>(&self, inflected_word: &str, dictionary: &'d crate::Dictionary) -> Result<Vec<&'d crate::DictionaryEntry>> {
         let affix_file = self.affix_file.as_ref()
-            .ok_or_else(|| RunmunchError::NoAffixFile)?;
+            .ok_or(RunmunchError::NoAffixFile)?;
 
-        let mut base_words = HashSet::new();
+        let mut entries = Vec::new();
 
-        // First, check if the word itself is in the dictionary
-        if let Some(entry) = dictionary.get_entry(inflected_word) {
-            // Verify that the word can generate itself (should always be true)
-            let expanded = self.expand_with_flags(inflected_word, &entry.flags)?;
-            if expanded.contains(&inflected_word.to_string()) {
-                base_words.insert(inflected_word.to_string());
+        // Check every candidate that the word itself or its stripped forms yield, and keep those
+        // whose dictionary flags really generate the inflected word
+        for candidate_base in Self::strip_candidates(affix_file, inflected_word) {
+            for entry in dictionary.get_entries(&candidate_base) {
+                let expanded = self.expand_with_flags(&candidate_base, &entry.flags)?;
+                if expanded.contains(&inflected_word.to_string()) {
+                    entries.push(entry);
+                }
             }
         }
 
-        // Try removing suffixes to find base forms
-        for (_flag, suffix_rules) in &affix_file.suffixes {
-            for rule in suffix_rules {
-                if let Some(candidate_base) = rule.reverse_apply(inflected_word, &AffixType::Suffix) {
-                    if let Some(entry) = dictionary.get_entry(&candidate_base) {
-                        // Check if this base word with its flags can generate the inflected word
-                        let expanded = self.expand_with_flags(&candidate_base, &entry.flags)?;
-                        if expanded.contains(&inflected_word.to_string()) {
-                            base_words.insert(candidate_base);
+        Ok(entries)
+    }
+
+    /// Undoes up to the affix model's number of suffixes and prefixes in every possible way,
+    /// returning the word itself and each candidate root.
+    fn strip_candidates(affix_file: &AffixFile, word: &str) -> HashSet<String> {
+        let mut candidates = HashSet::new();
+        candidates.insert(word.to_string());
+
+        for affix_type in [AffixType::Suffix, AffixType::Prefix] {
+            let rules = match affix_type {
+                AffixType::Prefix => &affix_file.prefixes,
+                AffixType::Suffix => &affix_file.suffixes,
+            };
+            let mut frontier: Vec<String> = candidates.iter().cloned().collect();
+
+            for _ in 0..affix_file.max_affixes(&affix_type) {
+                let mut next = Vec::new();
+                for current in &frontier {
+                    for rule in rules.values().flatten() {
+                        if let Some(stripped) = Self::unapply(affix_file, rule, current, &affix_type) {
+                            if candidates.insert(stripped.clone()) {
+                                next.push(stripped);
+                            }
                         }
                     }
                 }
+                frontier = next;
             }
         }
 
-        // Try removing prefixes to find base forms
-        for (_flag, prefix_rules) in &affix_file.prefixes {
-            for rule in prefix_rules {
-                if let Some(candidate_base) = rule.reverse_apply(inflected_word, &AffixType::Prefix) {
-                    if let Some(entry) = dictionary.get_entry(&candidate_base) {
-                        // Check if this base word with its flags can generate the inflected word
-                        let expanded = self.expand_with_flags(&candidate_base, &entry.flags)?;
-                        if expanded.contains(&inflected_word.to_string()) {
-                            base_words.insert(candidate_base);
+        candidates
+    }
+
+    /// Undoes `rule` on `word`, returning the root only if the rule really generates `word` from
+    /// it: its condition holds on the root and applying it gives `word` back.
+    fn unapply(affix_file: &AffixFile, rule: &AffixRule, word: &str, affix_type: &AffixType) -> Option<String> {
+        let root = affix_file.reverse_rule(rule, word, affix_type)?;
+        (affix_file.rule_applies(rule, &root, affix_type) && rule.apply(&root, affix_type) == word).then_some(root)
+    }
+
+    /// Like `strip_candidates`, but pairs each candidate root with the flags of every rule undone
+    /// on some way of reaching it.
+    fn strip_with_flags(affix_file: &AffixFile, word: &str) -> HashMap<String, Vec<String>> {
+        let mut candidates: HashMap<String, Vec<String>> = HashMap::new();
+        candidates.insert(word.to_string(), Vec::new());
+
+        for affix_type in [AffixType::Suffix, AffixType::Prefix] {
+            let rules = match affix_type {
+                AffixType::Prefix => &affix_file.prefixes,
+                AffixType::Suffix => &affix_file.suffixes,
+            };
+            let mut frontier: Vec<(String, Vec<String>)> = candidates.iter()
+                .map(|(candidate, flags)| (candidate.clone(), flags.clone()))
+                .collect();
+
+            for _ in 0..affix_file.max_affixes(&affix_type) {
+                let mut next = Vec::new();
+                for (current, flags) in &frontier {
+                    for rule in rules.values().flatten() {
+                        if let Some(stripped) = Self::unapply(affix_file, rule, current, &affix_type) {
+                            let mut stripped_flags = flags.clone();
+                            push_unique(&mut stripped_flags, std::slice::from_ref(&rule.flag));
+                            let known = candidates.entry(stripped.clone()).or_default();
+                            if stripped_flags.iter().any(|flag| !known.contains(flag)) {
+                                push_unique(known, &stripped_flags);
+                                next.push((stripped, stripped_flags));
+                            }
                         }
                     }
                 }
+                frontier = next;
             }
         }
 
-        // Try removing both prefixes and suffixes (for complex morphology)
-        for (_prefix_flag, prefix_rules) in &affix_file.prefixes {
-            for prefix_rule in prefix_rules {
-                if let Some(after_prefix_removal) = prefix_rule.reverse_apply(inflected_word, &AffixType::Prefix) {
-                    for (_suffix_flag, suffix_rules) in &affix_file.suffixes {
-                        for suffix_rule in suffix_rules {
-                            if let Some(candidate_base) = suffix_rule.reverse_apply(&after_prefix_removal, &AffixType::Suffix) {
-                                if let Some(entry) = dictionary.get_entry(&candidate_base) {
-                                    let expanded = self.expand_with_flags(&candidate_base, &entry.flags)?;
-                                    if expanded.contains(&inflected_word.to_string()) {
-                                        base_words.insert(candidate_base);
-                                    }
-                                }
-                            }
-                        }
+        candidates
+    }
+
+    /// Compresses a word list into dictionary entries, the inverse of unmunching: finds stems
+    /// and flags whose expansion covers `words` without generating any word outside the list.
+    ///
+    /// Every stem the words reduce to gets the flags that keep its expansion inside the list;
+    /// stems are then picked greedily, the one covering most of the words not yet covered first.
+    /// A stem that is not itself in the list is only used with the `NEEDAFFIX` flag, when the
+    /// affix file defines one. Words no rule accounts for become entries without flags.
+    pub fn munch(&self, words: &[String]) -> Result<crate::Dictionary> {
+        let affix_file = self.affix_file.as_ref()
+            .ok_or(RunmunchError::NoAffixFile)?;
+
+        let word_set: HashSet<&str> = words.iter().map(String::as_str).collect();
+
+        let mut stem_flags: HashMap<String, Vec<String>> = HashMap::new();
+        for word in &word_set {
+            for (stem, flags) in Self::strip_with_flags(affix_file, word) {
+                push_unique(stem_flags.entry(stem).or_default(), &flags);
+            }
+        }
+        let mut stems: Vec<(String, Vec<String>)> = stem_flags.into_iter().collect();
+        stems.sort();
+
+        // Each stem with the flags it may carry and the words it then generates
+        let mut candidates: Vec<(String, Vec<String>, Vec<String>)> = Vec::new();
+        for (stem, mut flags) in stems {
+            let mut chosen = Vec::new();
+            if !word_set.contains(stem.as_str()) {
+                match &affix_file.need_affix {
+                    Some(need_affix) => chosen.push(need_affix.clone()),
+                    None => continue,
+                }
+            }
+            let mut covered = self.expand_with_flags(&stem, &chosen)?;
+
+            flags.sort();
+            for flag in flags {
+                let mut trial = chosen.clone();
+                trial.push(flag);
+                let expanded = self.expand_with_flags(&stem, &trial)?;
+                if expanded.len() > covered.len() && expanded.iter().all(|form| word_set.contains(form.as_str())) {
+                    chosen = trial;
+                    covered = expanded;
+                }
+            }
+
+            if !covered.is_empty() {
+                candidates.push((stem, chosen, covered));
+            }
+        }
+
+        // Greedy set cover; a candidate's gain only shrinks as words get covered, so a popped
+        // candidate whose recounted gain is still the largest can be taken right away
+        let mut uncovered = word_set;
+        let mut queue: BinaryHeap<(usize, Reverse<usize>)> = candidates.iter()
+            .enumerate()
+            .map(|(index, (_, _, covered))| (covered.len(), Reverse(index)))
+            .collect();
+        let mut dictionary = crate::Dictionary::new();
+
+        while let Some((gain, Reverse(index))) = queue.pop() {
+            let (stem, flags, covered) = &candidates[index];
+            let current_gain = covered.iter().filter(|form| uncovered.contains(form.as_str())).count();
+            if current_gain == 0 {
+                continue;
+            }
+            if current_gain < gain {
+                queue.push((current_gain, Reverse(index)));
+                continue;
+            }
+
+            for form in covered {
+                uncovered.remove(form.as_str());
+            }
+            dictionary.insert(crate::DictionaryEntry {
+                word: stem.clone(),
+                flags: flags.clone(),
+                morph: Vec::new(),
+            });
+        }
+
+        let mut remaining: Vec<&str> = uncovered.into_iter().collect();
+        remaining.sort();
+        for word in remaining {
+            dictionary.insert(crate::DictionaryEntry {
+                word: word.to_string(),
+                flags: Vec::new(),
+                morph: Vec::new(),
+            });
+        }
+
+        dictionary.sort();
+        Ok(dictionary)
+    }
+
+    /// Undoes up to the affix model's number of prefixes (outermost first), then suffixes, in
+    /// every way whose forward application gives `word` back, returning each candidate root
+    /// with the derivation that would lead from it to `word`. Conditions are checked on the
+    /// candidate roots, and each affix stripped after the outermost one must name the flag of
+    /// the affix outside it in its continuation class.
+    fn reverse_derivations<'a>(affix_file: &'a AffixFile, word: &str) -> Vec<(String, Derivation<'a>)> {
+        let mut derivations = Vec::new();
+
+        for (stem, mut prefixes) in Self::strip_chains(affix_file, word, AffixType::Prefix) {
+            prefixes.reverse();
+            for (root, mut suffixes) in Self::strip_chains(affix_file, &stem, AffixType::Suffix) {
+                suffixes.reverse();
+                derivations.push((root, Derivation {
+                    word: word.to_string(),
+                    prefixes: prefixes.clone(),
+                    suffixes,
+                }));
+            }
+        }
+
+        derivations
+    }
+
+    /// Chains of affixes of one type that can be stripped from `word`, outermost first, starting
+    /// with the empty chain.
+    fn strip_chains<'a>(affix_file: &'a AffixFile, word: &str, affix_type: AffixType) -> Vec<(String, Vec<&'a AffixRule>)> {
+        let rules = match affix_type {
+            AffixType::Prefix => &affix_file.prefixes,
+            AffixType::Suffix => &affix_file.suffixes,
+        };
+        let mut chains: Vec<(String, Vec<&AffixRule>)> = vec![(word.to_string(), Vec::new())];
+        let mut next = 0;
+
+        while next < chains.len() && chains.len() < MAX_DERIVATIONS {
+            let (current, chain) = chains[next].clone();
+            next += 1;
+
+            if chain.len() >= affix_file.max_affixes(&affix_type) {
+                continue;
+            }
+            for rule in rules.values().flatten() {
+                if chain.last().is_some_and(|outer| !rule.continuation.contains(&outer.flag)) {
+                    continue;
+                }
+                if let Some(stripped) = Self::unapply(affix_file, rule, &current, &affix_type) {
+                    let mut extended = chain.clone();
+                    extended.push(rule);
+                    chains.push((stripped, extended));
+                }
+            }
+        }
+
+        chains
+    }
+
+    /// Looks `word` up the way a spellchecker does: strips affixes, respecting their
+    /// conditions, cross products and continuation classes, and returns the dictionary entries
+    /// that license one of the resulting derivations. A capitalized word is also looked up in
+    /// lowercase, and an all-uppercase word capitalized and in lowercase.
+    ///
+    /// Nothing is returned when the word is forbidden, or only used inside compounds.
+    pub fn lookup<'d>(&self, word: &str, dictionary: &'d crate::Dictionary) -> Result<Vec<&'d crate::DictionaryEntry>> {
+        let entries = self.lookup_with_status(word, dictionary)?;
+        Ok(entries.into_iter().map(|(entry, _)| entry).collect())
+    }
+
+    /// `lookup`, along with the status of the first derivation found for each entry.
+    fn lookup_with_status<'d>(&self, word: &str, dictionary: &'d crate::Dictionary) -> Result<Vec<(&'d crate::DictionaryEntry, WordStatus)>> {
+        let mut entries: Vec<(&crate::DictionaryEntry, WordStatus)> = Vec::new();
+        for (entry, _, status) in self.accepted_derivations(word, dictionary)? {
+            if !entries.iter().any(|(known, _)| std::ptr::eq(*known, entry)) {
+                entries.push((entry, status));
+            }
+        }
+        Ok(entries)
+    }
+
+    /// Every derivation of `word` (or of its case variants) from a dictionary entry that licenses
+    /// it, with the entry and the derivation's status. Nothing is returned when one of them
+    /// makes the word forbidden.
+    fn accepted_derivations<'d>(&self, word: &str, dictionary: &'d crate::Dictionary) -> Result<Vec<(&'d crate::DictionaryEntry, Derivation<'_>, WordStatus)>> {
+        let affix_file = self.affix_file.as_ref()
+            .ok_or(RunmunchError::NoAffixFile)?;
+
+        let mut accepted = Vec::new();
+        for variant in case_variants(word) {
+            for (root, derivation) in Self::reverse_derivations(affix_file, &variant) {
+                for entry in dictionary.get_entries(&root) {
+                    let flags = &entry.flags;
+                    if !derivation.is_licensed(affix_file, flags) || !derivation.is_surface_form(affix_file, flags) {
+                        continue;
+                    }
+                    let status = derivation.status(affix_file, flags);
+                    if status.forbidden {
+                        return Ok(Vec::new());
+                    }
+                    if status.is_standalone_word() {
+                        accepted.push((entry, derivation.clone(), status));
                     }
                 }
             }
         }
 
-        let mut sorted_results: Vec<String> = base_words.into_iter().collect();
-        sorted_results.sort();
-        Ok(sorted_results)
+        Ok(accepted)
+    }
+
+    /// Every morphological analysis of `word`: the dictionary entry it derives from, the rules
+    /// applied and the combined morphological fields (see `Analysis`). Words are found the way
+    /// `lookup` finds them.
+    pub fn analyze(&self, word: &str, dictionary: &crate::Dictionary) -> Result<Vec<Analysis>> {
+        let affix_file = self.affix_file.as_ref()
+            .ok_or(RunmunchError::NoAffixFile)?;
+
+        let mut analyses: Vec<Analysis> = Vec::new();
+        for (entry, derivation, _) in self.accepted_derivations(word, dictionary)? {
+            let mut root_morph = affix_file.expand_morph(&entry.morph);
+            if !root_morph.iter().any(|field| field.starts_with("st:")) {
+                root_morph.insert(0, format!("st:{}", entry.word));
+            }
+            let analysis = Analysis {
+                entry: entry.clone(),
+                prefixes: derivation.prefixes.iter().map(|rule| AppliedRule::new(affix_file, rule, AffixType::Prefix)).collect(),
+                suffixes: derivation.suffixes.iter().map(|rule| AppliedRule::new(affix_file, rule, AffixType::Suffix)).collect(),
+                morph: derivation.morph(&root_morph),
+            };
+            if !analyses.contains(&analysis) {
+                analyses.push(analysis);
+            }
+        }
+
+        Ok(analyses)
+    }
+
+    /// Whether `word` is correctly spelled: it is an affixed form of a dictionary entry (see
+    /// `lookup`), or it or one of its case variants is a compound allowed by the compounding
+    /// flags (see `check_compound`) or described by a `COMPOUNDRULE` pattern.
+    pub fn check(&self, word: &str, dictionary: &crate::Dictionary) -> Result<bool> {
+        Ok(!self.lookup(word, dictionary)?.is_empty() || self.is_compound(word, dictionary)?)
+    }
+
+    /// Whether `word`, or one of its case variants, is a compound of either kind.
+    fn is_compound(&self, word: &str, dictionary: &crate::Dictionary) -> Result<bool> {
+        for variant in case_variants(word) {
+            if self.check_compound(&variant, dictionary)? || self.check_compound_rules(&variant, dictionary)? {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Stems of `word`, one per distinct way it derives from a dictionary entry. The stem is the
+    /// entry's `st:` field, or its word when it has none. When suffixes with a `ds:`
+    /// (derivational suffix) field were applied, only the inflectional suffixes outside the last
+    /// of them are removed, so the stem is the derived word.
+    pub fn stem(&self, word: &str, dictionary: &crate::Dictionary) -> Result<Vec<String>> {
+        let affix_file = self.affix_file.as_ref()
+            .ok_or(RunmunchError::NoAffixFile)?;
+
+        let mut stems: Vec<String> = Vec::new();
+        for (entry, derivation, _) in self.accepted_derivations(word, dictionary)? {
+            let last_derivational = derivation.suffixes.iter()
+                .rposition(|rule| affix_file.expand_morph(&rule.morph).iter().any(|field| field.starts_with("ds:")));
+            let stem = match last_derivational {
+                Some(last) => derivation.suffixes[..=last].iter()
+                    .fold(entry.word.clone(), |stem, rule| rule.apply(&stem, &AffixType::Suffix)),
+                None => affix_file.expand_morph(&entry.morph).iter()
+                    .find_map(|field| field.strip_prefix("st:"))
+                    .unwrap_or(&entry.word)
+                    .to_string(),
+            };
+            if !stems.contains(&stem) {
+                stems.push(stem);
+            }
+        }
+
+        Ok(stems)
+    }
+
+    /// Forms of `lemma` whose inflection fields (see `inflection_fields`) include all of `tags`,
+    /// such as `is:gen_pl`. Every dictionary entry of `lemma` is expanded, along with entries
+    /// listed under it with an `st:` field, such as an irregular `went st:go is:past`. Without
+    /// tags all of their forms are returned.
+    pub fn generate(&self, lemma: &str, tags: &[String], dictionary: &crate::Dictionary) -> Result<Vec<String>> {
+        self.generate_matching(lemma, dictionary, |fields| tags.iter().all(|tag| fields.contains(tag)))
+    }
+
+    /// Forms of `lemma` inflected like `example`: those whose inflection fields are exactly those
+    /// of one of the analyses of `example`. For instance, with a dictionary
+    /// of verbs, `generate_like("drink", "walked", …)` gives the past tense of "drink".
+    pub fn generate_like(&self, lemma: &str, example: &str, dictionary: &crate::Dictionary) -> Result<Vec<String>> {
+        let affix_file = self.affix_file.as_ref()
+            .ok_or(RunmunchError::NoAffixFile)?;
+
+        let mut example_fields: Vec<Vec<String>> = Vec::new();
+        for (entry, derivation, _) in self.accepted_derivations(example, dictionary)? {
+            let mut fields = Self::inflection_fields(affix_file, entry, &derivation);
+            fields.sort();
+            if !example_fields.contains(&fields) {
+                example_fields.push(fields);
+            }
+        }
+
+        self.generate_matching(lemma, dictionary, |fields| {
+            let mut fields = fields.to_vec();
+            fields.sort();
+            example_fields.contains(&fields)
+        })
+    }
+
+    /// Forms of the dictionary entries of `lemma` whose inflection fields satisfy `matches`,
+    /// sorted.
+    fn generate_matching(&self, lemma: &str, dictionary: &crate::Dictionary, matches: impl Fn(&[String]) -> bool) -> Result<Vec<String>> {
+        let affix_file = self.affix_file.as_ref()
+            .ok_or(RunmunchError::NoAffixFile)?;
+
+        let mut forms = HashSet::new();
+        let entries = dictionary.iter().filter(|entry| {
+            entry.word == lemma || affix_file.expand_morph(&entry.morph).iter().any(|field| field.strip_prefix("st:") == Some(lemma))
+        });
+        for entry in entries {
+            let flags = &entry.flags;
+            for derivation in Self::derive(affix_file, &entry.word, flags) {
+                let fields = Self::inflection_fields(affix_file, entry, &derivation);
+                if derivation.status(affix_file, flags).is_standalone_word() && matches(&fields) {
+                    forms.insert(derivation.word);
+                }
+            }
+        }
+
+        let mut sorted_forms: Vec<String> = forms.into_iter().collect();
+        sorted_forms.sort();
+        Ok(sorted_forms)
+    }
+
+    /// Morphological fields telling how `derivation` inflects its lemma, with `AM` aliases
+    /// resolved: those of the rules applied and, when `entry` is listed under another stem (an
+    /// `st:` field other than its word), the entry's own fields except `st:` and `po:`.
+    fn inflection_fields(affix_file: &AffixFile, entry: &DictionaryEntry, derivation: &Derivation) -> Vec<String> {
+        let entry_fields = affix_file.expand_morph(&entry.morph);
+        let other_stem = entry_fields.iter()
+            .any(|field| field.strip_prefix("st:").is_some_and(|stem| stem != entry.word));
+
+        let mut fields = Vec::new();
+        if other_stem {
+            fields.extend(entry_fields.into_iter().filter(|field| !field.starts_with("st:") && !field.starts_with("po:")));
+        }
+        fields.extend(derivation.rules().flat_map(|rule| affix_file.expand_morph(&rule.morph)));
+        fields
+    }
+
+    /// Spelling suggestions for `word`, best first. Candidates come from the affix file's
+    /// `TRY`, `REP`, `MAP` and `KEY` tables (see `SuggestOptions::candidates`) and are kept when
+    /// the dictionary accepts them, every word of a multi-word candidate included. Words with the
+    /// `NOSUGGEST` flag are never suggested. When no candidate is accepted, the dictionary words
+    /// most similar to `word` are suggested instead, as far as `MAXDIFF` allows.
+    pub fn suggest(&self, word: &str, dictionary: &crate::Dictionary) -> Result<Vec<String>> {
+        let affix_file = self.affix_file.as_ref()
+            .ok_or(RunmunchError::NoAffixFile)?;
+
+        let mut suggestions = Vec::new();
+        for candidate in affix_file.suggest.candidates(word) {
+            let mut accepted = true;
+            for part in candidate.split(' ') {
+                if !self.is_suggestible(part, dictionary)? {
+                    accepted = false;
+                    break;
+                }
+            }
+            if accepted {
+                suggestions.push(candidate);
+                if suggestions.len() >= MAX_SUGGESTIONS {
+                    break;
+                }
+            }
+        }
+        if !suggestions.is_empty() {
+            return Ok(suggestions);
+        }
+
+        let threshold = affix_file.suggest.ngram_threshold();
+        let mut scored: Vec<(f64, &str)> = Vec::new();
+        for entry in dictionary.iter() {
+            let flags = &entry.flags;
+            let status = affix_file.word_status(flags);
+            if !status.is_standalone_word() || status.no_suggest || affix_file.needs_affix(flags) {
+                continue;
+            }
+            let similarity = ngram_similarity(word, &entry.word);
+            if similarity >= threshold && entry.word != word && !scored.iter().any(|(_, known)| *known == entry.word) {
+                scored.push((similarity, &entry.word));
+            }
+        }
+        scored.sort_by(|a, b| b.0.total_cmp(&a.0).then_with(|| a.1.cmp(b.1)));
+        Ok(scored.into_iter().take(MAX_NGRAM_SUGGESTIONS).map(|(_, word)| word.to_string()).collect())
+    }
+
+    /// Whether `word` is spelled correctly and may be suggested.
+    fn is_suggestible(&self, word: &str, dictionary: &crate::Dictionary) -> Result<bool> {
+        let entries = self.lookup_with_status(word, dictionary)?;
+        if entries.iter().any(|(_, status)| !status.no_suggest) {
+            return Ok(true);
+        }
+        Ok(entries.is_empty() && self.is_compound(word, dictionary)?)
     }
 
     pub fn find_base_and_expand(&self, inflected_word: &str, dictionary: &crate::Dictionary) -> Result<Vec<String>> {
-        let base_words = self.find_base_word(inflected_word, dictionary)?;
+        let base_entries = self.find_base_entries(inflected_word, dictionary)?;
 
-        if base_words.is_empty() {
+        if base_entries.is_empty() {
             // If no base word found, just return the original word
             return Ok(vec![inflected_word.to_string()]);
         }
 
         let mut all_expansions = HashSet::new();
 
-        for base_word in base_words {
-            if let Some(entry) = dictionary.get_entry(&base_word) {
-                let expanded = self.expand_with_flags(&base_word, &entry.flags)?;
-                for word in expanded {
-                    all_expansions.insert(word);
-                }
+        for entry in base_entries {
+            let expanded = self.expand_with_flags(&entry.word, &entry.flags)?;
+            for word in expanded {
+                all_expansions.insert(word);
             }
         }
 
@@ -299,4 +1425,336 @@ SFX S 0 s .
         assert!(result.contains(&"cats".to_string()));
         assert!(result.contains(&"cated".to_string())); // Note: This is grammatically incorrect but follows the rules
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_expand_twofold_suffix() {
+        let affix_content = r#"
+SFX A Y 1
+SFX A 0 ing/B .
+
+SFX B Y 1
+SFX B 0 s .
+"#;
+        let mut expander = WordExpander::new();
+        expander.set_affix_file(&AffixFile::parse(affix_content).unwrap());
+
+        let result = expander.expand_with_flags("walk", &["A".to_string()]).unwrap();
+        assert_eq!(result, vec!["walk", "walking", "walkings"]);
+    }
+
+    #[test]
+    fn test_expand_continuation_prefixes() {
+        let affix_content = r#"
+PFX P Y 1
+PFX P 0 re/Q .
+
+PFX Q Y 1
+PFX Q 0 un .
+
+SFX S Y 1
+SFX S 0 s/Q .
+"#;
+        let mut expander = WordExpander::new();
+        expander.set_affix_file(&AffixFile::parse(affix_content).unwrap());
+
+        // Only one prefix is allowed without COMPLEXPREFIXES
+        let result = expander.expand_with_flags("do", &["P".to_string()]).unwrap();
+        assert_eq!(result, vec!["do", "redo"]);
+
+        // A suffix continuation class can license a prefix the root does not carry
+        let result = expander.expand_with_flags("do", &["S".to_string()]).unwrap();
+        assert_eq!(result, vec!["do", "dos", "undos"]);
+    }
+
+    #[test]
+    fn test_expand_complex_prefixes() {
+        let affix_content = r#"
+COMPLEXPREFIXES
+
+PFX P Y 1
+PFX P 0 re/Q .
+
+PFX Q Y 1
+PFX Q 0 un .
+
+SFX A Y 1
+SFX A 0 ing/B .
+
+SFX B Y 1
+SFX B 0 s .
+"#;
+        let mut expander = WordExpander::new();
+        expander.set_affix_file(&AffixFile::parse(affix_content).unwrap());
+
+        let result = expander.expand_with_flags("do", &["P".to_string()]).unwrap();
+        assert_eq!(result, vec!["do", "redo", "unredo"]);
+
+        // Only one suffix is allowed with COMPLEXPREFIXES
+        let result = expander.expand_with_flags("do", &["A".to_string()]).unwrap();
+        assert_eq!(result, vec!["do", "doing"]);
+    }
+
+    #[test]
+    fn test_expand_with_morphology() {
+        let affix_content = r#"
+AM 2
+AM is:pl
+AM is:gen
+
+SFX S Y 1
+SFX S 0 s . 1
+
+SFX G Y 1
+SFX G 0 's . is:gen
+"#;
+        let mut expander = WordExpander::new();
+        expander.set_affix_file(&AffixFile::parse(affix_content).unwrap());
+
+        let forms = expander.expand_with_morphology(
+            "dog",
+            &["S".to_string(), "G".to_string()],
+            &["po:noun".to_string()],
+        ).unwrap();
+
+        let tags = |word: &str| forms.iter().find(|form| form.word == word).map(|form| form.morph.clone());
+        assert_eq!(tags("dog"), Some(vec!["po:noun".to_string()]));
+        assert_eq!(tags("dogs"), Some(vec!["po:noun".to_string(), "is:pl".to_string()]));
+        assert_eq!(tags("dog's"), Some(vec!["po:noun".to_string(), "is:gen".to_string()]));
+    }
+
+    #[test]
+    fn test_expand_circumfix() {
+        let affix_content = r#"
+CIRCUMFIX X
+
+PFX G Y 1
+PFX G 0 ge/X .
+
+SFX T Y 1
+SFX T 0 t/X .
+
+SFX E Y 1
+SFX E 0 en .
+"#;
+        let mut expander = WordExpander::new();
+        expander.set_affix_file(&AffixFile::parse(affix_content).unwrap());
+
+        let flags = vec!["G".to_string(), "T".to_string(), "E".to_string()];
+        let result = expander.expand_with_flags("mach", &flags).unwrap();
+        assert_eq!(result, vec!["gemacht", "mach", "machen"]);
+    }
+
+    #[test]
+    fn test_fullstrip() {
+        let affix_content = r#"
+SFX A Y 2
+SFX A go went go
+SFX A 0 s .
+"#;
+        let dictionary = crate::Dictionary::parse("1\ngo/A\n").unwrap();
+        let flags = vec!["A".to_string()];
+
+        let mut affix_file = AffixFile::parse(affix_content).unwrap();
+        let mut expander = WordExpander::new();
+        expander.set_affix_file(&affix_file);
+        assert_eq!(expander.expand_with_flags("go", &flags).unwrap(), vec!["go", "gos"]);
+        assert!(expander.find_base_word("went", &dictionary).unwrap().is_empty());
+
+        affix_file.fullstrip = true;
+        expander.set_affix_file(&affix_file);
+        assert_eq!(expander.expand_with_flags("go", &flags).unwrap(), vec!["go", "gos", "went"]);
+        assert_eq!(expander.find_base_word("went", &dictionary).unwrap(), vec!["go"]);
+    }
+
+    #[test]
+    fn test_expand_need_affix() {
+        let affix_content = r#"
+NEEDAFFIX N
+
+SFX A Y 1
+SFX A 0 s/BN .
+
+SFX B Y 1
+SFX B 0 x .
+
+SFX C Y 1
+SFX C 0 y .
+"#;
+        let mut expander = WordExpander::new();
+        expander.set_affix_file(&AffixFile::parse(affix_content).unwrap());
+
+        let result = expander.expand_with_flags("foo", &["N".to_string(), "C".to_string()]).unwrap();
+        assert_eq!(result, vec!["fooy"]);
+
+        let result = expander.expand_with_flags("foo", &["A".to_string()]).unwrap();
+        assert_eq!(result, vec!["foo", "foosx"]);
+    }
+
+    #[test]
+    fn test_strip_candidates_checks_conditions() {
+        let affix_content = "SFX D Y 2\nSFX D 0 ed o\nSFX D 0 d e\n";
+        let affix_file = AffixFile::parse(affix_content).unwrap();
+
+        let candidates = WordExpander::strip_candidates(&affix_file, "agreed");
+        assert!(candidates.contains("agree"));
+        // "agre" does not end in "o", so "0 ed o" cannot have produced "agreed" from it
+        assert!(!candidates.contains("agre"));
+    }
+
+    #[test]
+    fn test_expand_word_status() {
+        let affix_content = r#"
+FORBIDDENWORD !
+SUBSTANDARD ?
+
+SFX S Y 2
+SFX S 0 s .
+SFX S 0 z/? .
+
+SFX X Y 1
+SFX X 0 x/! .
+"#;
+        let mut expander = WordExpander::new();
+        expander.set_affix_file(&AffixFile::parse(affix_content).unwrap());
+
+        let flags = vec!["S".to_string(), "X".to_string()];
+        let result = expander.expand_with_flags("cat", &flags).unwrap();
+        assert_eq!(result, vec!["cat", "cats", "catz"]);
+
+        let forms = expander.expand_with_morphology("cat", &flags, &[]).unwrap();
+        let substandard: Vec<&str> = forms.iter()
+            .filter(|form| form.status.substandard)
+            .map(|form| form.word.as_str())
+            .collect();
+        assert_eq!(substandard, vec!["catz"]);
+
+        let result = expander.expand_with_flags("dog", &["!".to_string(), "S".to_string()]).unwrap();
+        assert!(result.is_empty());
+    }
+
+    #[test]
+    fn test_expand_compounds() {
+        let affix_content = r#"
+COMPOUNDBEGIN B
+COMPOUNDMIDDLE M
+COMPOUNDEND E
+ONLYINCOMPOUND O
+COMPOUNDPERMITFLAG P
+COMPOUNDMIN 3
+
+SFX S Y 1
+SFX S 0 s .
+
+SFX F Y 1
+SFX F 0 s/BMOP .
+"#;
+        let mut expander = WordExpander::new();
+        expander.set_affix_file(&AffixFile::parse(affix_content).unwrap());
+        let dictionary = crate::Dictionary::parse("4\nhaus/BSZ\ntür/E\nbahn/MEF\nab/BEZ\n").unwrap();
+
+        let limits = CompoundLimits::new(2, 30);
+        let compounds = expander.expand_compounds(&dictionary, &limits).unwrap();
+        // The suffixed "haus" cannot start a compound, "ab" is shorter than COMPOUNDMIN
+        assert_eq!(compounds, vec!["bahnsbahn", "bahnsbahns", "bahnstür", "hausbahn", "hausbahns", "haustür"]);
+
+        let limits = CompoundLimits::new(3, 12).with_heads(vec!["tür".to_string()]);
+        let compounds = expander.expand_compounds(&dictionary, &limits).unwrap();
+        assert_eq!(compounds, vec!["bahnsbahntür", "bahnstür", "hausbahnstür", "hausbahntür", "haustür"]);
+
+        // Compound-only forms are not words on their own
+        let result = expander.expand_with_flags("bahn", &["F".to_string()]).unwrap();
+        assert_eq!(result, vec!["bahn"]);
+    }
+
+    #[test]
+    fn test_munch() {
+        let affix_content = r#"
+NEEDAFFIX N
+
+SFX S Y 1
+SFX S 0 s .
+
+SFX D Y 2
+SFX D 0 ed [^e]
+SFX D 0 d e
+
+SFX G Y 1
+SFX G e ing e
+"#;
+        let mut expander = WordExpander::new();
+        expander.set_affix_file(&AffixFile::parse(affix_content).unwrap());
+
+        let words: Vec<String> = ["work", "works", "worked", "bake", "baked", "baking", "cats", "cat", "dog", "walked", "walking"]
+            .iter().map(|word| word.to_string()).collect();
+        let dictionary = expander.munch(&words).unwrap();
+        let entries: Vec<(String, Vec<String>)> = dictionary.entries().map(|(word, flags)| (word.clone(), flags.clone())).collect();
+
+        // "walke" is not in the list, so it only generates its affixed forms
+        assert_eq!(entries, vec![
+            ("bake".to_string(), vec!["D".to_string(), "G".to_string()]),
+            ("cat".to_string(), vec!["S".to_string()]),
+            ("dog".to_string(), vec![]),
+            ("walke".to_string(), vec!["N".to_string(), "D".to_string(), "G".to_string()]),
+            ("work".to_string(), vec!["D".to_string(), "S".to_string()]),
+        ]);
+
+        let mut unmunched: Vec<String> = Vec::new();
+        for (word, flags) in dictionary.entries() {
+            unmunched.extend(expander.expand_with_flags(word, flags).unwrap());
+        }
+        unmunched.sort();
+        let mut expected = words.clone();
+        expected.sort();
+        assert_eq!(unmunched, expected);
+    }
+
+    #[test]
+    fn test_lookup() {
+        let affix_content = r#"
+FORBIDDENWORD !
+
+PFX U Y 1
+PFX U 0 un .
+
+PFX R N 1
+PFX R 0 re .
+
+SFX S Y 2
+SFX S y ies [^aeiou]y
+SFX S 0 s [aeiou]y
+
+SFX D Y 1
+SFX D 0 ed/X .
+
+SFX X Y 1
+SFX X 0 ly .
+"#;
+        let affix_file = AffixFile::parse(affix_content).unwrap();
+        let mut expander = WordExpander::new();
+        expander.set_affix_file(&affix_file);
+        let dictionary = crate::Dictionary::parse_with_affix_file("5\nfly/US\nplay/URSD\nParis\nplays/!\nbook\n", &affix_file).unwrap();
+
+        let roots = |word: &str| -> Vec<String> {
+            expander.lookup(word, &dictionary).unwrap().iter().map(|entry| entry.word.clone()).collect()
+        };
+        assert_eq!(roots("flies"), vec!["fly"]);
+        assert_eq!(roots("unflies"), vec!["fly"]);
+        assert_eq!(roots("playedly"), vec!["play"]);
+        assert_eq!(roots("Unplayed"), vec!["play"]);
+        assert_eq!(roots("PARIS"), vec!["Paris"]);
+
+        // The condition, the missing cross product, the continuation class and case all matter
+        assert!(roots("flys").is_empty());
+        assert!(roots("replayed").is_empty());
+        assert!(roots("flyedly").is_empty());
+        assert!(roots("paris").is_empty());
+        // Forbidden even though "play" generates it
+        assert!(roots("plays").is_empty());
+
+        for (word, flags) in dictionary.entries() {
+            for form in expander.expand_with_flags(word, flags).unwrap() {
+                assert_eq!(expander.check(&form, &dictionary).unwrap(), form != "plays", "{}", form);
+            }
+        }
+    }
+}
@@ -1,11 +1,22 @@
 pub mod affix;
+pub mod compound;
+pub mod condition;
+pub mod conversion;
+pub mod diagnostics;
 pub mod dictionary;
+pub mod encoding;
 pub mod expander;
+pub mod suggest;
 pub mod error;
 
-pub use affix::{AffixFile, AffixRule, AffixType};
-pub use dictionary::Dictionary;
-pub use expander::WordExpander;
+pub use affix::{AffixFile, AffixRule, AffixType, WordStatus};
+pub use compound::{CompoundLimits, CompoundOptions};
+pub use condition::Condition;
+pub use conversion::ConversionTable;
+pub use diagnostics::{Diagnostic, DiagnosticCode, ParseMode, Severity};
+pub use dictionary::{Dictionary, DictionaryEntry};
+pub use expander::{Analysis, AppliedRule, DerivedForm, ExpandedForm, WordExpander};
+pub use suggest::SuggestOptions;
 pub use error::{RunmunchError, Result};
 
 use std::collections::HashSet;
@@ -14,6 +25,11 @@ pub struct Runmunch {
     affix_file: Option<AffixFile>,
     dictionary: Option<Dictionary>,
     expander: WordExpander,
+    /// Forms of `FORBIDDENWORD` entries, excluded from output whichever root generates them.
+    forbidden_forms: HashSet<String>,
+    convert_input: bool,
+    convert_output: bool,
+    parse_mode: ParseMode,
 }
 
 impl Runmunch {
@@ -22,30 +38,101 @@ impl Runmunch {
             affix_file: None,
             dictionary: None,
             expander: WordExpander::new(),
+            forbidden_forms: HashSet::new(),
+            convert_input: true,
+            convert_output: true,
+            parse_mode: ParseMode::Strict,
         }
     }
 
+    /// Sets how subsequently loaded files react to parse errors (strict by default).
+    pub fn set_parse_mode(&mut self, mode: ParseMode) {
+        self.parse_mode = mode;
+    }
+
+    /// Problems found while loading the affix file and the dictionary.
+    pub fn diagnostics(&self) -> impl Iterator<Item = &Diagnostic> {
+        let affix = self.affix_file.iter().flat_map(|affix_file| affix_file.diagnostics.iter());
+        let dictionary = self.dictionary.iter().flat_map(|dictionary| dictionary.diagnostics().iter());
+        affix.chain(dictionary)
+    }
+
+    /// Enables or disables the `ICONV` table on input words (enabled by default).
+    pub fn set_input_conversion(&mut self, enabled: bool) {
+        self.convert_input = enabled;
+    }
+
+    /// Enables or disables the `OCONV` table on output words (enabled by default).
+    pub fn set_output_conversion(&mut self, enabled: bool) {
+        self.convert_output = enabled;
+    }
+
+    fn convert_input(&self, word: &str) -> String {
+        match &self.affix_file {
+            Some(affix_file) if self.convert_input => affix_file.iconv.convert(word),
+            _ => word.to_string(),
+        }
+    }
+
+    fn convert_output(&self, word: &str) -> String {
+        match &self.affix_file {
+            Some(affix_file) if self.convert_output => affix_file.oconv.convert(word),
+            _ => word.to_string(),
+        }
+    }
+
+    /// Applies `OCONV` to generated words, dropping duplicates the conversion may create.
+    fn convert_outputs(&self, words: Vec<String>) -> Vec<String> {
+        let mut seen = HashSet::new();
+        words.into_iter()
+            .map(|word| self.convert_output(&word))
+            .filter(|word| seen.insert(word.clone()))
+            .collect()
+    }
+
     pub fn load_affix_file<P: AsRef<std::path::Path>>(&mut self, path: P) -> Result<()> {
-        let affix_file = AffixFile::load(path)?;
+        let affix_file = AffixFile::load_with_mode(path, self.parse_mode)?;
         self.expander.set_affix_file(&affix_file);
         self.affix_file = Some(affix_file);
-        Ok(())
+        self.refresh_forbidden_forms()
     }
 
+    /// Loads a dictionary. When an affix file has already been loaded, the dictionary is decoded
+    /// with its `SET` charset and its flags are split according to its `FLAG` type.
     pub fn load_dictionary<P: AsRef<std::path::Path>>(&mut self, path: P) -> Result<()> {
-        let dictionary = Dictionary::load(path)?;
+        let dictionary = Dictionary::load_with_mode(path, self.affix_file.as_ref(), self.parse_mode)?;
         self.dictionary = Some(dictionary);
+        self.refresh_forbidden_forms()
+    }
+
+    fn refresh_forbidden_forms(&mut self) -> Result<()> {
+        self.forbidden_forms = match (&self.affix_file, &self.dictionary) {
+            (Some(_), Some(dictionary)) => self.expander.forbidden_forms(dictionary)?,
+            _ => HashSet::new(),
+        };
         Ok(())
     }
 
+    pub fn affix_file(&self) -> Option<&AffixFile> {
+        self.affix_file.as_ref()
+    }
+
+    pub fn dictionary(&self) -> Option<&Dictionary> {
+        self.dictionary.as_ref()
+    }
+
     pub fn expand_word(&self, word: &str) -> Result<Vec<String>> {
-        self.expander.expand(word)
+        let mut expanded = self.expander.expand(&self.convert_input(word))?;
+        expanded.retain(|word| !self.forbidden_forms.contains(word));
+        Ok(self.convert_outputs(expanded))
     }
 
     pub fn find_base_and_expand(&self, inflected_word: &str) -> Result<Vec<String>> {
         let dictionary = self.dictionary.as_ref()
-            .ok_or_else(|| RunmunchError::NoDictionary)?;
-        self.expander.find_base_and_expand(inflected_word, dictionary)
+            .ok_or(RunmunchError::NoDictionary)?;
+        let mut expanded = self.expander.find_base_and_expand(&self.convert_input(inflected_word), dictionary)?;
+        expanded.retain(|word| !self.forbidden_forms.contains(word));
+        Ok(self.convert_outputs(expanded))
     }
 
     pub fn expand_words(&self, words: &[String]) -> Result<Vec<String>> {
@@ -66,7 +153,7 @@ impl Runmunch {
 
     pub fn unmunch(&self) -> Result<Vec<String>> {
         let dictionary = self.dictionary.as_ref()
-            .ok_or_else(|| RunmunchError::NoDictionary)?;
+            .ok_or(RunmunchError::NoDictionary)?;
 
         let mut result = Vec::new();
         let mut seen = HashSet::new();
@@ -79,6 +166,10 @@ impl Runmunch {
             };
 
             for expanded_word in expanded {
+                if self.forbidden_forms.contains(&expanded_word) {
+                    continue;
+                }
+                let expanded_word = self.convert_output(&expanded_word);
                 if seen.insert(expanded_word.clone()) {
                     result.push(expanded_word);
                 }
@@ -87,6 +178,190 @@ impl Runmunch {
 
         Ok(result)
     }
+
+    /// Dictionary entries the word is a form of, found by stripping affixes instead of expanding
+    /// entries; see `WordExpander::lookup`. Forbidden forms have no entries.
+    pub fn lookup(&self, word: &str) -> Result<Vec<&DictionaryEntry>> {
+        let dictionary = self.dictionary.as_ref()
+            .ok_or(RunmunchError::NoDictionary)?;
+        let word = self.convert_input(word);
+        if self.forbidden_forms.contains(&word) {
+            return Ok(Vec::new());
+        }
+        self.expander.lookup(&word, dictionary)
+    }
+
+    /// Whether the word is correctly spelled according to the affix file and dictionary.
+    pub fn check(&self, word: &str) -> Result<bool> {
+        let dictionary = self.dictionary.as_ref()
+            .ok_or(RunmunchError::NoDictionary)?;
+        let word = self.convert_input(word);
+        if self.forbidden_forms.contains(&word) {
+            return Ok(false);
+        }
+        self.expander.check(&word, dictionary)
+    }
+
+    /// Every morphological analysis of the word; see `WordExpander::analyze`. Forbidden forms
+    /// have none.
+    pub fn analyze(&self, word: &str) -> Result<Vec<Analysis>> {
+        let dictionary = self.dictionary.as_ref()
+            .ok_or(RunmunchError::NoDictionary)?;
+        let word = self.convert_input(word);
+        if self.forbidden_forms.contains(&word) {
+            return Ok(Vec::new());
+        }
+        self.expander.analyze(&word, dictionary)
+    }
+
+    /// Stems of the word; see `WordExpander::stem`. Forbidden forms have none.
+    pub fn stem(&self, word: &str) -> Result<Vec<String>> {
+        let dictionary = self.dictionary.as_ref()
+            .ok_or(RunmunchError::NoDictionary)?;
+        let word = self.convert_input(word);
+        if self.forbidden_forms.contains(&word) {
+            return Ok(Vec::new());
+        }
+        let stems = self.expander.stem(&word, dictionary)?;
+        Ok(self.convert_outputs(stems))
+    }
+
+    /// Forms of the lemma carrying all of `tags`; see `WordExpander::generate`.
+    pub fn generate(&self, lemma: &str, tags: &[String]) -> Result<Vec<String>> {
+        let dictionary = self.dictionary.as_ref()
+            .ok_or(RunmunchError::NoDictionary)?;
+        let mut forms = self.expander.generate(&self.convert_input(lemma), tags, dictionary)?;
+        forms.retain(|form| !self.forbidden_forms.contains(form));
+        Ok(self.convert_outputs(forms))
+    }
+
+    /// Forms of the lemma inflected like `example`; see `WordExpander::generate_like`.
+    pub fn generate_like(&self, lemma: &str, example: &str) -> Result<Vec<String>> {
+        let dictionary = self.dictionary.as_ref()
+            .ok_or(RunmunchError::NoDictionary)?;
+        let mut forms = self.expander.generate_like(&self.convert_input(lemma), &self.convert_input(example), dictionary)?;
+        forms.retain(|form| !self.forbidden_forms.contains(form));
+        Ok(self.convert_outputs(forms))
+    }
+
+    /// Spelling suggestions for the word, best first; see `WordExpander::suggest`.
+    pub fn suggest(&self, word: &str) -> Result<Vec<String>> {
+        let dictionary = self.dictionary.as_ref()
+            .ok_or(RunmunchError::NoDictionary)?;
+        let mut suggestions = self.expander.suggest(&self.convert_input(word), dictionary)?;
+        suggestions.retain(|suggestion| !suggestion.split(' ').any(|part| self.forbidden_forms.contains(part)));
+        Ok(self.convert_outputs(suggestions))
+    }
+
+    /// Compresses a word list into dictionary entries using the loaded affix file's rules; see
+    /// `WordExpander::munch`. Words go through `ICONV` first.
+    pub fn munch(&self, words: &[String]) -> Result<Dictionary> {
+        let words: Vec<String> = words.iter().map(|word| self.convert_input(word)).collect();
+        self.expander.munch(&words)
+    }
+
+    /// Generates the compounds allowed by the affix file's compounding directives and
+    /// `COMPOUNDRULE` patterns, within `limits`.
+    pub fn unmunch_compounds(&self, limits: &CompoundLimits) -> Result<Vec<String>> {
+        let dictionary = self.dictionary.as_ref()
+            .ok_or(RunmunchError::NoDictionary)?;
+
+        let mut compounds = self.expander.expand_compounds(dictionary, limits)?;
+        compounds.extend(self.expander.expand_compound_rules(dictionary, limits)?);
+        compounds.retain(|word| !self.forbidden_forms.contains(word));
+        let mut compounds = self.convert_outputs(compounds);
+        compounds.sort();
+        Ok(compounds)
+    }
+
+    /// Whether the word is a compound described by one of the `COMPOUNDRULE` patterns.
+    pub fn is_compound_rule_word(&self, word: &str) -> Result<bool> {
+        let dictionary = self.dictionary.as_ref()
+            .ok_or(RunmunchError::NoDictionary)?;
+        let word = self.convert_input(word);
+        if self.forbidden_forms.contains(&word) {
+            return Ok(false);
+        }
+        self.expander.check_compound_rules(&word, dictionary)
+    }
+
+    /// Expands every dictionary entry, keeping the morphological fields and entry status of each
+    /// generated form.
+    pub fn unmunch_with_morphology(&self) -> Result<Vec<ExpandedForm>> {
+        let dictionary = self.dictionary.as_ref()
+            .ok_or(RunmunchError::NoDictionary)?;
+
+        let mut result = Vec::new();
+        let mut seen = HashSet::new();
+
+        for entry in dictionary.iter() {
+            for mut form in self.expander.expand_with_morphology(&entry.word, &entry.flags, &entry.morph)? {
+                if self.forbidden_forms.contains(&form.word) {
+                    continue;
+                }
+                form.word = self.convert_output(&form.word);
+                if seen.insert(form.clone()) {
+                    result.push(form);
+                }
+            }
+        }
+
+        Ok(result)
+    }
+
+    /// Expands every dictionary entry, recording for each form the entry and the rules that
+    /// produced it. Forms generated in several ways are listed once per derivation.
+    pub fn unmunch_with_provenance(&self) -> Result<Vec<DerivedForm>> {
+        let dictionary = self.dictionary.as_ref()
+            .ok_or(RunmunchError::NoDictionary)?;
+
+        let mut result = Vec::new();
+        for entry in dictionary.iter() {
+            result.extend(self.expand_entry_with_provenance(entry)?);
+        }
+        Ok(result)
+    }
+
+    /// The forms of one dictionary entry with the rules behind each, without forbidden forms and
+    /// with `OCONV` applied. An entry may have no forms, e.g. when it is only used in compounds.
+    pub fn expand_entry_with_provenance(&self, entry: &DictionaryEntry) -> Result<Vec<DerivedForm>> {
+        let forms = self.expander.expand_with_provenance(entry)?;
+        Ok(self.convert_derived_forms(forms))
+    }
+
+    /// `expand_word` with the rule behind each form.
+    pub fn expand_word_with_provenance(&self, word: &str) -> Result<Vec<DerivedForm>> {
+        let forms = self.expander.expand_all_with_provenance(&self.convert_input(word))?;
+        Ok(self.convert_derived_forms(forms))
+    }
+
+    /// `find_base_and_expand` with the entry and rules behind each form. Nothing is returned
+    /// when no dictionary entry generates the word.
+    pub fn find_base_with_provenance(&self, inflected_word: &str) -> Result<Vec<DerivedForm>> {
+        let dictionary = self.dictionary.as_ref()
+            .ok_or(RunmunchError::NoDictionary)?;
+
+        let mut result = Vec::new();
+        for entry in self.expander.find_base_entries(&self.convert_input(inflected_word), dictionary)? {
+            for form in self.expander.expand_with_provenance(entry)? {
+                if !result.contains(&form) {
+                    result.push(form);
+                }
+            }
+        }
+        Ok(self.convert_derived_forms(result))
+    }
+
+    /// Drops forbidden forms and applies `OCONV` to the rest.
+    fn convert_derived_forms(&self, forms: Vec<DerivedForm>) -> Vec<DerivedForm> {
+        forms.into_iter()
+            .filter(|form| !self.forbidden_forms.contains(&form.word))
+            .map(|mut form| {
+                form.word = self.convert_output(&form.word);
+                form
+            })
+            .collect()
+    }
 }
 
 impl Default for Runmunch {
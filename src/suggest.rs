@@ -0,0 +1,244 @@
+/// Keyboard layout used for `KEY` suggestions when the affix file does not declare one.
+pub const DEFAULT_KEYBOARD: &str = "qwertyuiop|asdfghjkl|zxcvbnm";
+
+/// `MAXDIFF` value used when the affix file does not set one.
+pub const DEFAULT_MAX_DIFF: usize = 5;
+
+/// Upper bound on the number of suggestions returned for one word.
+pub const MAX_SUGGESTIONS: usize = 15;
+
+/// Upper bound on the number of dictionary words suggested for their n-gram similarity.
+pub const MAX_NGRAM_SUGGESTIONS: usize = 4;
+
+/// Suggestion directives of an affix file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SuggestOptions {
+    /// `TRY`: characters inserted or substituted when looking for suggestions, most frequent first.
+    pub try_chars: String,
+    /// `REP`: common misspellings and their corrections. `^` and `$` anchor a pattern to the
+    /// start or end of the word; `_` stands for a space.
+    pub replacements: Vec<(String, String)>,
+    /// `MAP`: groups of related characters; a parenthesized sequence counts as one element.
+    pub map: Vec<Vec<String>>,
+    /// `KEY`: keyboard rows separated by `|`. Neighbouring keys are tried as typos.
+    pub keyboard: Option<String>,
+    /// `MAXDIFF`: from 0 to 10, how dissimilar n-gram suggestions may be from the word.
+    pub max_diff: Option<usize>,
+    /// `NOSPLITSUGS`: never suggest splitting the word in two.
+    pub no_split: bool,
+}
+
+impl SuggestOptions {
+    pub fn new() -> Self {
+        SuggestOptions {
+            try_chars: String::new(),
+            replacements: Vec::new(),
+            map: Vec::new(),
+            keyboard: None,
+            max_diff: None,
+            no_split: false,
+        }
+    }
+
+    /// Splits a `MAP` entry such as `aáà(ss)ß` into its elements.
+    pub fn parse_map(entry: &str) -> Vec<String> {
+        let mut elements = Vec::new();
+        let mut chars = entry.chars();
+        while let Some(c) = chars.next() {
+            if c == '(' {
+                let sequence: String = chars.by_ref().take_while(|&c| c != ')').collect();
+                elements.push(sequence);
+            } else {
+                elements.push(c.to_string());
+            }
+        }
+        elements
+    }
+
+    /// Writes `MAP` elements back, parenthesizing multi-character ones.
+    pub fn format_map(elements: &[String]) -> String {
+        elements.iter()
+            .map(|element| if element.chars().count() == 1 { element.clone() } else { format!("({})", element) })
+            .collect()
+    }
+
+    /// Minimum n-gram similarity, between 0 and 1, of a dictionary word to be suggested.
+    pub fn ngram_threshold(&self) -> f64 {
+        let max_diff = self.max_diff.unwrap_or(DEFAULT_MAX_DIFF).min(10);
+        (10 - max_diff) as f64 / 10.0
+    }
+
+    /// Candidate corrections of `word`, most likely first: capitalization, `REP` replacements,
+    /// `MAP` substitutions, neighbouring `KEY` keys, swapped, extra, forgotten and wrong
+    /// characters (from `TRY`), and finally two-word splits. Candidates may contain spaces;
+    /// `word` itself is never among them.
+    pub fn candidates(&self, word: &str) -> Vec<String> {
+        let chars: Vec<char> = word.chars().collect();
+        let mut candidates = Vec::new();
+
+        let lower = word.to_lowercase();
+        let mut lower_chars = lower.chars();
+        if let Some(first) = lower_chars.next() {
+            candidates.push(first.to_uppercase().chain(lower_chars).collect());
+        }
+        candidates.push(word.to_uppercase());
+
+        for (from, to) in &self.replacements {
+            candidates.extend(Self::replace(word, from, to));
+        }
+
+        for group in &self.map {
+            for element in group {
+                for (index, _) in word.match_indices(element.as_str()) {
+                    for other in group.iter().filter(|other| *other != element) {
+                        candidates.push(format!("{}{}{}", &word[..index], other, &word[index + element.len()..]));
+                    }
+                }
+            }
+        }
+
+        let keyboard = self.keyboard.as_deref().unwrap_or(DEFAULT_KEYBOARD);
+        for (index, &c) in chars.iter().enumerate() {
+            for row in keyboard.split('|') {
+                let keys: Vec<char> = row.chars().collect();
+                for position in keys.iter().enumerate().filter(|(_, &key)| key == c).map(|(position, _)| position) {
+                    let neighbours = [position.checked_sub(1), Some(position + 1)];
+                    for &key in neighbours.into_iter().flatten().filter_map(|neighbour| keys.get(neighbour)) {
+                        candidates.push(Self::with_char(&chars, index, Some(key)));
+                    }
+                }
+            }
+        }
+
+        for index in 1..chars.len() {
+            let mut swapped = chars.clone();
+            swapped.swap(index - 1, index);
+            candidates.push(swapped.into_iter().collect());
+        }
+
+        for index in 0..chars.len() {
+            candidates.push(Self::with_char(&chars, index, None));
+        }
+
+        for index in 0..=chars.len() {
+            for c in self.try_chars.chars() {
+                let mut inserted = chars.clone();
+                inserted.insert(index, c);
+                candidates.push(inserted.into_iter().collect());
+            }
+        }
+
+        for index in 0..chars.len() {
+            for c in self.try_chars.chars().filter(|&c| c != chars[index]) {
+                candidates.push(Self::with_char(&chars, index, Some(c)));
+            }
+        }
+
+        if !self.no_split {
+            for index in 1..chars.len() {
+                let (first, second): (String, String) = (chars[..index].iter().collect(), chars[index..].iter().collect());
+                candidates.push(format!("{} {}", first, second));
+            }
+        }
+
+        let mut seen = std::collections::HashSet::new();
+        candidates.retain(|candidate| candidate != word && seen.insert(candidate.clone()));
+        candidates
+    }
+
+    /// `chars` with the character at `index` replaced by `replacement`, or removed.
+    fn with_char(chars: &[char], index: usize, replacement: Option<char>) -> String {
+        let mut changed = chars.to_vec();
+        match replacement {
+            Some(c) => changed[index] = c,
+            None => {
+                changed.remove(index);
+            }
+        }
+        changed.into_iter().collect()
+    }
+
+    /// Every way of applying one `REP` replacement to `word`.
+    fn replace(word: &str, from: &str, to: &str) -> Vec<String> {
+        let at_start = from.starts_with('^');
+        let at_end = from.len() > 1 && from.ends_with('$');
+        let pattern = from.trim_start_matches('^').trim_end_matches('$').replace('_', " ");
+        let replacement = to.replace('_', " ");
+        if pattern.is_empty() {
+            return Vec::new();
+        }
+
+        word.match_indices(pattern.as_str())
+            .filter(|(index, _)| (!at_start || *index == 0) && (!at_end || index + pattern.len() == word.len()))
+            .map(|(index, _)| format!("{}{}{}", &word[..index], replacement, &word[index + pattern.len()..]))
+            .collect()
+    }
+}
+
+impl Default for SuggestOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Similarity of two words between 0 and 1: the share of their character 1-, 2- and 3-grams
+/// found in the other word.
+pub fn ngram_similarity(a: &str, b: &str) -> f64 {
+    let a: Vec<char> = a.to_lowercase().chars().collect();
+    let b: Vec<char> = b.to_lowercase().chars().collect();
+
+    let mut shared = 0;
+    let mut total = 0;
+    for n in 1..=3 {
+        for (word, other) in [(&a, &b), (&b, &a)] {
+            for gram in word.windows(n) {
+                total += 1;
+                if other.windows(n).any(|candidate| candidate == gram) {
+                    shared += 1;
+                }
+            }
+        }
+    }
+
+    if total == 0 {
+        0.0
+    } else {
+        shared as f64 / total as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_candidates() {
+        let mut options = SuggestOptions::new();
+        options.try_chars = "l".to_string();
+        options.replacements.push(("^alot$".to_string(), "a_lot".to_string()));
+        options.map.push(SuggestOptions::parse_map("u(uu)ü"));
+        options.no_split = true;
+
+        let candidates = options.candidates("helo");
+        assert_eq!(&candidates[..2], ["Helo", "HELO"]);
+        assert!(candidates.contains(&"hello".to_string()));
+        assert!(candidates.contains(&"hlo".to_string()));
+        assert!(candidates.contains(&"hleo".to_string()));
+        assert!(candidates.contains(&"gelo".to_string()));
+        assert!(!candidates.iter().any(|candidate| candidate.contains(' ')));
+
+        assert!(options.candidates("alot").contains(&"a lot".to_string()));
+        assert!(!options.candidates("alots").contains(&"a lots".to_string()));
+        let candidates = options.candidates("fur");
+        assert!(candidates.contains(&"für".to_string()));
+        assert!(candidates.contains(&"fuur".to_string()));
+        assert_eq!(SuggestOptions::format_map(&options.map[0]), "u(uu)ü");
+    }
+
+    #[test]
+    fn test_ngram_similarity() {
+        assert_eq!(ngram_similarity("house", "house"), 1.0);
+        assert_eq!(ngram_similarity("cat", "dog"), 0.0);
+        assert!(ngram_similarity("helo", "hello") > ngram_similarity("helo", "halt"));
+    }
+}
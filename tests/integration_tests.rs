@@ -30,6 +30,28 @@ SFX S 0 s .
     assert_eq!(ed_rules[0].affix, "ed");
 }
 
+#[test]
+fn test_continuation_flags_parsing() {
+    let affix_content = r#"
+SFX A Y 2
+SFX A 0 ing/B .
+SFX A 0 0/B .
+
+SFX B Y 1
+SFX B 0 s .
+"#;
+
+    let affix_file = AffixFile::parse(affix_content).expect("Should parse affix file");
+    let a_rules = affix_file.get_suffix_rules("A").expect("Should have A suffix rules");
+    assert_eq!(a_rules[0].affix, "ing");
+    assert_eq!(a_rules[0].continuation, vec!["B"]);
+    assert_eq!(a_rules[1].affix, "");
+    assert_eq!(a_rules[1].continuation, vec!["B"]);
+
+    let b_rules = affix_file.get_suffix_rules("B").expect("Should have B suffix rules");
+    assert!(b_rules[0].continuation.is_empty());
+}
+
 #[test]
 fn test_word_expansion() {
     let affix_content = r#"
@@ -74,9 +96,33 @@ test/UN,S
     assert_eq!(test_entry.flags, vec!["U", "N", ",", "S"]); // Mixed format parses character by character
 }
 
+#[test]
+fn test_morphological_fields() {
+    let affix_content = r#"
+SFX S Y 1
+SFX S 0 s . is:pl
+"#;
+    let affix_file = AffixFile::parse(affix_content).expect("Should parse affix file");
+    let s_rules = affix_file.get_suffix_rules("S").expect("Should have S suffix rules");
+    assert_eq!(s_rules[0].morph, vec!["is:pl"]);
+
+    let dict_content = "2\ncat/S\tpo:noun\nrun po:verb st:run\n";
+    let dictionary = Dictionary::parse(dict_content).expect("Should parse dictionary");
+
+    let cat_entry = dictionary.get_entry("cat").expect("Should have cat entry");
+    assert_eq!(cat_entry.flags, vec!["S"]);
+    assert_eq!(cat_entry.morph, vec!["po:noun"]);
+
+    let run_entry = dictionary.get_entry("run").expect("Should have run entry");
+    assert!(run_entry.flags.is_empty());
+    assert_eq!(run_entry.morph, vec!["po:verb", "st:run"]);
+}
+
 #[test]
 fn test_full_runmunch_workflow() {
     let affix_content = r#"
+FLAG long
+
 PFX UN Y 1
 PFX UN 0 un .
 
@@ -88,8 +134,6 @@ SFX ED 0 ed .
 happy/UN
 work/ED
 "#;
-    
-    use std::io::Write;
     use std::fs;
     
     let mut runmunch = Runmunch::new();
@@ -107,7 +151,383 @@ work/ED
     assert!(results.contains(&"unhappy".to_string()));
     assert!(results.contains(&"work".to_string()));
     assert!(results.contains(&"worked".to_string()));
-    assert_eq!(results.len(), 5); // Note: includes "workeded" due to current expansion logic
+    assert_eq!(results.len(), 4); // Suffixes only stack through continuation flags
+}
+
+#[test]
+fn test_find_base_word_with_complex_prefixes() {
+    let affix_content = r#"
+COMPLEXPREFIXES
+
+PFX W Y 1
+PFX W 0 w/L .
+
+PFX L Y 1
+PFX L 0 l .
+
+SFX M Y 1
+SFX M 0 im .
+"#;
+    let dict_content = "1\nsefer/WMZ\n";
+
+    let affix_file = AffixFile::parse(affix_content).expect("Should parse affix file");
+    let dictionary = Dictionary::parse(dict_content).expect("Should parse dictionary");
+    let mut expander = WordExpander::new();
+    expander.set_affix_file(&affix_file);
+
+    let base = expander.find_base_word("lwsefer", &dictionary).expect("Should find base");
+    assert_eq!(base, vec!["sefer"]);
+
+    let base = expander.find_base_word("wseferim", &dictionary).expect("Should find base");
+    assert_eq!(base, vec!["sefer"]);
+}
+
+#[test]
+fn test_unmunch_excludes_forbidden_words() {
+    let affix_content = r#"
+FORBIDDENWORD !
+
+SFX S Y 1
+SFX S 0 s .
+"#;
+    let dict_content = "3\nsheep/S\nsheeps/!\nlamb/S\n";
+
+    std::fs::write("/tmp/test_forbidden.aff", affix_content).expect("Should write affix file");
+    std::fs::write("/tmp/test_forbidden.dic", dict_content).expect("Should write dict file");
+
+    let mut runmunch = Runmunch::new();
+    runmunch.load_affix_file("/tmp/test_forbidden.aff").expect("Should load affix file");
+    runmunch.load_dictionary("/tmp/test_forbidden.dic").expect("Should load dictionary file");
+
+    let mut results = runmunch.unmunch().expect("Should generate expanded words");
+    results.sort();
+    assert_eq!(results, vec!["lamb", "lambs", "sheep"]);
+
+    let expanded = runmunch.expand_words(&["sheep".to_string(), "lamb".to_string()]).expect("Should expand words");
+    assert!(!expanded.contains(&"sheeps".to_string()));
+    assert!(expanded.contains(&"lambs".to_string()));
+}
+
+#[test]
+fn test_compound_rules() {
+    let affix_content = r#"
+COMPOUNDMIN 1
+COMPOUNDRULE 1
+COMPOUNDRULE n*1t
+"#;
+    let dict_content = "3\n1/n1\n2/n\n1th/t\n";
+
+    std::fs::write("/tmp/test_compoundrule.aff", affix_content).expect("Should write affix file");
+    std::fs::write("/tmp/test_compoundrule.dic", dict_content).expect("Should write dict file");
+
+    let mut runmunch = Runmunch::new();
+    runmunch.load_affix_file("/tmp/test_compoundrule.aff").expect("Should load affix file");
+    runmunch.load_dictionary("/tmp/test_compoundrule.dic").expect("Should load dictionary file");
+
+    let affix_file = runmunch.affix_file().expect("Should have affix file");
+    assert_eq!(affix_file.compound.rules.len(), 1);
+    assert_eq!(affix_file.compound.rules[0].pattern, "n*1t");
+
+    assert!(runmunch.is_compound_rule_word("11th").unwrap());
+    assert!(runmunch.is_compound_rule_word("211th").unwrap());
+    assert!(!runmunch.is_compound_rule_word("21th").unwrap());
+    assert!(!runmunch.is_compound_rule_word("1th").unwrap());
+
+    let compounds = runmunch.unmunch_compounds(&CompoundLimits::new(3, 5)).unwrap();
+    assert_eq!(compounds, vec!["111th", "11th", "211th"]);
+}
+
+#[test]
+fn test_legacy_encoded_files() {
+    // ISO-8859-2 with CRLF line endings: "kuća" is stored as ku\xE6a
+    let affix_bytes = b"SET ISO8859-2\r\n\r\nSFX A Y 1\r\nSFX A a e a\r\n";
+    let dict_bytes = b"1\r\nku\xE6a/A\r\n";
+
+    std::fs::write("/tmp/test_legacy.aff", affix_bytes).expect("Should write affix file");
+    std::fs::write("/tmp/test_legacy.dic", dict_bytes).expect("Should write dict file");
+
+    let mut runmunch = Runmunch::new();
+    runmunch.load_affix_file("/tmp/test_legacy.aff").expect("Should load affix file");
+    runmunch.load_dictionary("/tmp/test_legacy.dic").expect("Should load dictionary file");
+
+    let affix_file = runmunch.affix_file().expect("Should have affix file");
+    assert_eq!(affix_file.encoding.as_deref(), Some("ISO8859-2"));
+
+    let mut results = runmunch.unmunch().expect("Should generate expanded words");
+    results.sort();
+    assert_eq!(results, vec!["kuća", "kuće"]);
+}
+
+#[test]
+fn test_affix_file_round_trip() {
+    let affix_content = r#"SET UTF-8
+FLAG long
+COMPLEXPREFIXES
+FORBIDDENWORD !!
+COMPOUNDFLAG Cc
+COMPOUNDMIN 2
+COMPOUNDRULE 1
+COMPOUNDRULE (Nn)*(Tt)
+
+AF 2
+AF AaBb
+AF Bb
+
+AM 1
+AM po:noun
+
+ICONV 1
+ICONV ’ '
+
+PFX Un Y 1
+PFX Un 0 un/1 [^u] is:neg
+
+SFX Aa N 2
+SFX Aa y ies [^aeiou]y 1
+SFX Aa 0 s [aeiou]y
+
+SFX Bb Y 1
+SFX Bb 0 ed/2 . is:past
+"#;
+
+    let original = AffixFile::parse(affix_content).expect("Should parse affix file");
+    let written = original.serialize();
+    let reparsed = AffixFile::parse(&written).expect("Should parse written affix file");
+
+    assert_eq!(reparsed.prefixes, original.prefixes);
+    assert_eq!(reparsed.suffixes, original.suffixes);
+    assert_eq!(reparsed.flag_type, original.flag_type);
+    assert_eq!(reparsed.complex_prefixes, original.complex_prefixes);
+    assert_eq!(reparsed.forbidden_word, original.forbidden_word);
+    assert_eq!(reparsed.compound.flag, original.compound.flag);
+    assert_eq!(reparsed.compound.min_length, 2);
+    assert_eq!(reparsed.compound.rules, original.compound.rules);
+    assert_eq!(reparsed.iconv, original.iconv);
+    assert!(reparsed.diagnostics.is_empty());
+    assert!(written.contains("SFX Aa N 2\nSFX Aa y ies [^aeiou]y 1\n"));
+
+    // The morphological fields "is:neg" and "is:past" are added to the AM table
+    assert_eq!(reparsed.morph_aliases.len(), 3);
+    assert_eq!(reparsed.serialize(), written);
+
+    original.save("/tmp/test_round_trip.aff").expect("Should write affix file");
+    let loaded = AffixFile::load("/tmp/test_round_trip.aff").expect("Should load written affix file");
+    assert_eq!(loaded.suffixes, original.suffixes);
+}
+
+#[test]
+fn test_dictionary_round_trip() {
+    let affix_content = "FLAG long\nAF 2\nAF AaBb\nAF Bb\n\nSFX Aa Y 1\nSFX Aa 0 s .\n\nSFX Bb Y 1\nSFX Bb 0 ed .\n";
+    let dict_content = "2\nwalk/1\tpo:verb\nkm\\/h\n";
+
+    let affix_file = AffixFile::parse(affix_content).expect("Should parse affix file");
+    let mut dictionary = Dictionary::parse_with_affix_file(dict_content, &affix_file).expect("Should parse dictionary");
+
+    dictionary.insert(DictionaryEntry { word: "talk".to_string(), flags: vec!["Bb".to_string()], morph: Vec::new() });
+    dictionary.sort();
+    dictionary.save("/tmp/test_round_trip.dic", Some(&affix_file)).expect("Should write dictionary");
+
+    let written = std::fs::read_to_string("/tmp/test_round_trip.dic").expect("Should read dictionary");
+    assert_eq!(written, "3\nkm\\/h\ntalk/2\nwalk/1\tpo:verb\n");
+
+    let reloaded = Dictionary::load_with_affix_file("/tmp/test_round_trip.dic", &affix_file).expect("Should load dictionary");
+    assert!(reloaded.diagnostics().is_empty());
+    let resolved = |dictionary: &Dictionary| -> Vec<_> {
+        dictionary.iter()
+            .map(|entry| (entry.word.clone(), entry.flags.clone(), entry.morph.clone()))
+            .collect()
+    };
+    assert_eq!(resolved(&reloaded), resolved(&dictionary));
+}
+
+#[test]
+fn test_parse_diagnostics() {
+    let affix_content = r#"FLAG long
+FROBNICATE yes
+
+SFX Aa Y 3
+SFX Aa 0 s .
+SFX Aa y ies [^aeiou
+
+SFX Aa Y 1
+SFX Aa 0 ed .
+"#;
+    let dict_content = "3\nfly/Aa\ncat/Abc\n";
+
+    std::fs::write("/tmp/test_diagnostics.aff", affix_content).expect("Should write affix file");
+    std::fs::write("/tmp/test_diagnostics.dic", dict_content).expect("Should write dict file");
+
+    let error = AffixFile::parse(affix_content).expect_err("Bad condition is fatal in strict mode");
+    assert!(error.to_string().contains("line 6, column 14"));
+
+    let mut runmunch = Runmunch::new();
+    runmunch.set_parse_mode(ParseMode::Lenient);
+    runmunch.load_affix_file("/tmp/test_diagnostics.aff").expect("Should load affix file");
+    runmunch.load_dictionary("/tmp/test_diagnostics.dic").expect("Should load dictionary file");
+
+    let found: Vec<_> = runmunch.diagnostics()
+        .map(|diagnostic| (diagnostic.line, diagnostic.column, diagnostic.severity, diagnostic.code))
+        .collect();
+    assert_eq!(found, vec![
+        (2, 1, Severity::Warning, DiagnosticCode::UnknownDirective),
+        (6, 14, Severity::Error, DiagnosticCode::BadCondition),
+        (4, 10, Severity::Warning, DiagnosticCode::RuleCountMismatch),
+        (8, 5, Severity::Warning, DiagnosticCode::DuplicateFlagBlock),
+        (3, 5, Severity::Error, DiagnosticCode::InvalidFlag),
+        (1, 1, Severity::Warning, DiagnosticCode::EntryCountMismatch),
+    ]);
+    let first = runmunch.diagnostics().next().expect("Should have diagnostics");
+    assert_eq!(first.to_string(), "/tmp/test_diagnostics.aff:2:1: warning[unknown-directive]: Unknown directive FROBNICATE");
+
+    // The duplicate block is merged and the malformed lines are skipped
+    let mut results = runmunch.unmunch().expect("Should generate expanded words");
+    results.sort();
+    assert_eq!(results, vec!["fly", "flyed", "flys"]);
+}
+
+#[test]
+fn test_rule_shaped_like_header() {
+    // "SFX N Y 0" strips "Y" and adds nothing; the declared count makes it a rule, not a header
+    let affix_content = "SFX N Y 2\nSFX N Y 0\nSFX N 0 s .\n\nSFX M Y 1\nSFX M 0 ed .\n";
+    let affix_file = AffixFile::parse(affix_content).expect("Should parse affix file");
+    assert!(affix_file.diagnostics.is_empty());
+
+    let rules = affix_file.get_suffix_rules("N").expect("Should have N rules");
+    assert_eq!(rules.len(), 2);
+    assert_eq!(rules[0].strip, "Y");
+    assert!(rules[0].affix.is_empty());
+    assert_eq!(affix_file.get_suffix_rules("M").map(|rules| rules.len()), Some(1));
+}
+
+#[test]
+fn test_dictionary_homonyms() {
+    let affix_content = r#"
+SFX S Y 1
+SFX S 0 s .
+
+SFX D Y 1
+SFX D 0 ed .
+"#;
+    let dict_content = "3\nbank/S\tpo:noun\nbank/D\tpo:verb\nkm\\/h\n";
+
+    std::fs::write("/tmp/test_homonyms.aff", affix_content).expect("Should write affix file");
+    std::fs::write("/tmp/test_homonyms.dic", dict_content).expect("Should write dict file");
+
+    let mut runmunch = Runmunch::new();
+    runmunch.load_affix_file("/tmp/test_homonyms.aff").expect("Should load affix file");
+    runmunch.load_dictionary("/tmp/test_homonyms.dic").expect("Should load dictionary file");
+
+    let mut results = runmunch.unmunch().expect("Should generate expanded words");
+    results.sort();
+    assert_eq!(results, vec!["bank", "banked", "banks", "km/h"]);
+
+    // Only the verb entry generates "banked"
+    let expanded = runmunch.find_base_and_expand("banked").expect("Should find base word");
+    assert_eq!(expanded, vec!["bank", "banked"]);
+}
+
+#[test]
+fn test_affix_conditions() {
+    let affix_content = r#"
+PFX U Y 1
+PFX U 0 un [^u]n
+
+SFX Y Y 2
+SFX Y y ies [^aeiou]y
+SFX Y 0 s [aeiou]y
+"#;
+
+    let affix_file = AffixFile::parse(affix_content).expect("Should parse affix file");
+    let mut expander = WordExpander::new();
+    expander.set_affix_file(&affix_file);
+
+    let expand = |word: &str, flag: &str| {
+        let mut forms = expander.expand_with_flags(word, &[flag.to_string()]).expect("Should expand word");
+        forms.sort();
+        forms
+    };
+
+    assert_eq!(expand("fly", "Y"), vec!["flies", "fly"]);
+    assert_eq!(expand("boy", "Y"), vec!["boy", "boys"]);
+
+    // Prefix conditions are checked at the start of the word
+    assert_eq!(expand("known", "U"), vec!["known", "unknown"]);
+    assert_eq!(expand("unknown", "U"), vec!["unknown"]);
+    assert_eq!(expand("nerve", "U"), vec!["nerve"]);
+}
+
+#[test]
+fn test_alias_tables() {
+    let affix_content = r#"FLAG num
+
+AF 2
+AF 1,2 # 1
+AF 2 # 2
+
+AM 2
+AM is:pl
+AM po:noun
+
+SFX 1 Y 1
+SFX 1 0 s . 1
+
+SFX 2 Y 1
+SFX 2 0 ed .
+"#;
+    let dict_content = "2\ncat/2 2\nwalk/1\n";
+
+    let affix_file = AffixFile::parse(affix_content).expect("Should parse affix file");
+    assert_eq!(affix_file.flag_aliases.len(), 2);
+    assert_eq!(affix_file.flag_aliases["1"], vec!["1", "2"]);
+    assert_eq!(affix_file.morph_aliases["2"], vec!["po:noun"]);
+    assert_eq!(affix_file.get_suffix_rules("1").unwrap()[0].morph, vec!["is:pl"]);
+
+    let dictionary = Dictionary::parse_with_affix_file(dict_content, &affix_file).expect("Should parse dictionary");
+    let mut expander = WordExpander::new();
+    expander.set_affix_file(&affix_file);
+    let cat = dictionary.get_entry("cat").expect("Should have cat entry");
+    let mut forms = expander.expand_with_flags(&cat.word, &cat.flags).expect("Should expand word");
+    forms.sort();
+    assert_eq!(forms, vec!["cat", "cated"]);
+
+    let error = Dictionary::parse_with_affix_file("1\ncat/3\n", &affix_file).expect_err("Alias 3 is undefined");
+    assert!(matches!(error, RunmunchError::InvalidFlag(_)));
+}
+
+#[test]
+fn test_conversion_tables() {
+    let affix_content = r#"ICONV 1
+ICONV ’ '
+
+OCONV 1
+OCONV ' ’
+
+SFX S Y 1
+SFX S 0 's .
+"#;
+    let dict_content = r#"1
+rock/S
+"#;
+
+    std::fs::write("/tmp/test_conv.aff", affix_content).expect("Should write affix file");
+    std::fs::write("/tmp/test_conv.dic", dict_content).expect("Should write dict file");
+
+    let mut runmunch = Runmunch::new();
+    runmunch.load_affix_file("/tmp/test_conv.aff").expect("Should load affix file");
+    runmunch.load_dictionary("/tmp/test_conv.dic").expect("Should load dictionary file");
+
+    let mut results = runmunch.unmunch().expect("Should generate expanded words");
+    results.sort();
+    assert_eq!(results, vec!["rock", "rock’s"]);
+
+    // The typographic apostrophe in the input is normalized before the lookup
+    let found = runmunch.find_base_and_expand("rock’s").expect("Should find base word");
+    assert!(found.contains(&"rock’s".to_string()));
+
+    runmunch.set_output_conversion(false);
+    let mut results = runmunch.unmunch().expect("Should generate expanded words");
+    results.sort();
+    assert_eq!(results, vec!["rock", "rock's"]);
 }
 
 #[cfg(test)]
@@ -135,4 +555,423 @@ mod hunspell_hr_tests {
             println!("Expanded {} words from Croatian dictionary", expanded.len());
         }
     }
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_munch_round_trip() {
+    let affix_content = r#"FLAG long
+PFX Un Y 1
+PFX Un 0 un .
+
+SFX Ss Y 2
+SFX Ss y ies [^aeiou]y
+SFX Ss 0 s [aeiou]y
+
+SFX Ed Y 1
+SFX Ed 0 ed .
+"#;
+    std::fs::write("/tmp/test_munch.aff", affix_content).expect("Should write affix file");
+
+    let mut runmunch = Runmunch::new();
+    runmunch.load_affix_file("/tmp/test_munch.aff").expect("Should load affix file");
+
+    let words: Vec<String> = ["fly", "flies", "boy", "boys", "lock", "locked", "unlock", "unlocked", "pony"]
+        .iter().map(|word| word.to_string()).collect();
+    let dictionary = runmunch.munch(&words).expect("Should munch words");
+    let content = dictionary.serialize(runmunch.affix_file()).expect("Should serialize dictionary");
+    assert_eq!(content, "4\nboy/Ss\nfly/Ss\nlock/EdUn\npony\n");
+
+    std::fs::write("/tmp/test_munch.dic", content).expect("Should write dict file");
+    runmunch.load_dictionary("/tmp/test_munch.dic").expect("Should load dictionary");
+    let mut unmunched = runmunch.unmunch().expect("Should unmunch");
+    unmunched.sort();
+    let mut expected = words.clone();
+    expected.sort();
+    assert_eq!(unmunched, expected);
+}
+
+#[test]
+fn test_munch_round_trip_with_flag_aliases() {
+    // With numeric flags, flag 1 must not be taken for alias 1
+    let affix_content = "FLAG num\nAF 1\nAF 2,1\n\nSFX 1 Y 1\nSFX 1 0 s .\n\nSFX 2 Y 1\nSFX 2 0 ed .\n";
+    std::fs::write("/tmp/test_munch_af.aff", affix_content).expect("Should write affix file");
+
+    let mut runmunch = Runmunch::new();
+    runmunch.load_affix_file("/tmp/test_munch_af.aff").expect("Should load affix file");
+
+    let words: Vec<String> = ["work", "works", "worked", "cat", "cats"].iter().map(|word| word.to_string()).collect();
+    let dictionary = runmunch.munch(&words).expect("Should munch words");
+    assert!(matches!(dictionary.serialize(runmunch.affix_file()), Err(RunmunchError::InvalidFlag(_))));
+
+    let mut affix_file = runmunch.affix_file().unwrap().clone();
+    dictionary.save_with_aliases("/tmp/test_munch_af.dic", &mut affix_file).expect("Should save dictionary");
+    affix_file.save("/tmp/test_munch_af.aff").expect("Should save affix file");
+    assert_eq!(std::fs::read_to_string("/tmp/test_munch_af.dic").unwrap(), "2\ncat/2\nwork/1\n");
+
+    let mut runmunch = Runmunch::new();
+    runmunch.load_affix_file("/tmp/test_munch_af.aff").expect("Should load saved affix file");
+    runmunch.load_dictionary("/tmp/test_munch_af.dic").expect("Should load saved dictionary");
+    let mut unmunched = runmunch.unmunch().expect("Should unmunch");
+    unmunched.sort();
+    let mut expected = words.clone();
+    expected.sort();
+    assert_eq!(unmunched, expected);
+}
+
+#[test]
+fn test_spell_check() {
+    let affix_content = r#"ICONV 1
+ICONV ’ '
+FORBIDDENWORD !
+COMPOUNDMIN 1
+COMPOUNDRULE 1
+COMPOUNDRULE nm
+
+SFX S Y 1
+SFX S 0 s .
+
+SFX P Y 1
+SFX P 0 's .
+"#;
+    let dict_content = "6\nwork/SP\nbook/S\nbooks/!\nOslo/P\n1/n\n2/nm\n";
+
+    std::fs::write("/tmp/test_spell_check.aff", affix_content).expect("Should write affix file");
+    std::fs::write("/tmp/test_spell_check.dic", dict_content).expect("Should write dict file");
+
+    let mut runmunch = Runmunch::new();
+    runmunch.load_affix_file("/tmp/test_spell_check.aff").expect("Should load affix file");
+    runmunch.load_dictionary("/tmp/test_spell_check.dic").expect("Should load dictionary");
+
+    for word in ["work", "works", "Works", "WORK", "work's", "work’s", "Oslo's", "OSLO'S", "12"] {
+        assert!(runmunch.check(word).unwrap(), "{} should be accepted", word);
+    }
+    for word in ["worked", "books", "oslo", "wORK", "21"] {
+        assert!(!runmunch.check(word).unwrap(), "{} should be rejected", word);
+    }
+
+    let entries = runmunch.lookup("WORKS").unwrap();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].word, "work");
+    assert_eq!(entries[0].flags, vec!["S", "P"]);
+}
+
+#[test]
+fn test_spell_check_compounds() {
+    let affix_content = r#"COMPOUNDFLAG X
+COMPOUNDBEGIN B
+COMPOUNDEND E
+COMPOUNDMIN 3
+ONLYINCOMPOUND O
+
+SFX S Y 1
+SFX S 0 s .
+"#;
+    let dict_content = "6\nfoot/XS\nball/XS\nhand/B\nwork/E\nkit/O\nin\n";
+
+    std::fs::write("/tmp/test_spell_check_compounds.aff", affix_content).expect("Should write affix file");
+    std::fs::write("/tmp/test_spell_check_compounds.dic", dict_content).expect("Should write dict file");
+
+    let mut runmunch = Runmunch::new();
+    runmunch.load_affix_file("/tmp/test_spell_check_compounds.aff").expect("Should load affix file");
+    runmunch.load_dictionary("/tmp/test_spell_check_compounds.dic").expect("Should load dictionary");
+
+    let compounds = runmunch.unmunch_compounds(&CompoundLimits::new(3, 30)).unwrap();
+    assert!(compounds.contains(&"football".to_string()));
+    for word in ["football", "footballs", "Football", "FOOTBALL", "handwork", "footballfoot"] {
+        assert!(runmunch.check(word).unwrap(), "{} should be accepted", word);
+    }
+    for word in ["footsball", "workhand", "footin", "kit", "footkit"] {
+        assert!(!runmunch.check(word).unwrap(), "{} should be rejected", word);
+    }
+    assert_eq!(runmunch.suggest("footblal").unwrap()[0], "football");
+}
+
+#[test]
+fn test_suggestions() {
+    let affix_content = r#"TRY esianrtolcdugmphbyfvkwz
+KEY qwertyuiop|asdfghjkl|zxcvbnm
+MAXDIFF 5
+NOSUGGEST !
+REP 2
+REP f ph
+REP ^alot$ a_lot
+MAP 1
+MAP uü
+
+SFX S Y 1
+SFX S 0 s .
+"#;
+    let dict_content = "8\nphone/S\nwork/S\na\nlot\nmüde\ndamn/!\nhouse\nmouse\n";
+
+    std::fs::write("/tmp/test_suggest.aff", affix_content).expect("Should write affix file");
+    std::fs::write("/tmp/test_suggest.dic", dict_content).expect("Should write dict file");
+
+    let mut runmunch = Runmunch::new();
+    runmunch.load_affix_file("/tmp/test_suggest.aff").expect("Should load affix file");
+    runmunch.load_dictionary("/tmp/test_suggest.dic").expect("Should load dictionary");
+
+    assert_eq!(runmunch.suggest("fones").unwrap(), vec!["phones"]);
+    assert_eq!(runmunch.suggest("alot").unwrap(), vec!["a lot", "lot"]);
+    assert_eq!(runmunch.suggest("mude").unwrap(), vec!["müde"]);
+    assert_eq!(runmunch.suggest("eork").unwrap(), vec!["work"]);
+    assert_eq!(runmunch.suggest("Wrok").unwrap(), vec!["Work"]);
+    assert_eq!(runmunch.suggest("workss").unwrap(), vec!["works"]);
+    // NOSUGGEST words are accepted but never suggested
+    assert!(runmunch.check("damn").unwrap());
+    assert!(runmunch.suggest("damm").unwrap().is_empty());
+    // Nothing is one edit away, so the most similar dictionary words are suggested
+    assert_eq!(runmunch.suggest("houzze").unwrap(), vec!["house"]);
+
+    let affix_file = runmunch.affix_file().unwrap();
+    let reparsed = AffixFile::parse(&affix_file.serialize()).expect("Should parse written affix file");
+    assert_eq!(reparsed.suggest, affix_file.suggest);
+}
+
+#[test]
+fn test_morphological_analysis() {
+    let affix_content = r#"FLAG long
+AM 2
+AM po:noun
+AM po:verb
+
+PFX Un Y 1
+PFX Un 0 un . dp:neg
+
+SFX Ss Y 2
+SFX Ss 0 s . is:pl
+SFX Ss 0 s . is:3sg
+"#;
+    let dict_content = "3\nwork/Ss\tpo:verb\nbook/SsUn\t1\ngo\tst:went po:verb\n";
+
+    std::fs::write("/tmp/test_analyze.aff", affix_content).expect("Should write affix file");
+    std::fs::write("/tmp/test_analyze.dic", dict_content).expect("Should write dict file");
+
+    let mut runmunch = Runmunch::new();
+    runmunch.load_affix_file("/tmp/test_analyze.aff").expect("Should load affix file");
+    runmunch.load_dictionary("/tmp/test_analyze.dic").expect("Should load dictionary");
+
+    let analyses = runmunch.analyze("unbooks").unwrap();
+    assert_eq!(analyses.len(), 2);
+    assert_eq!(analyses[0].entry.word, "book");
+    assert_eq!(analyses[0].prefixes, vec![AppliedRule { affix_type: AffixType::Prefix, flag: "Un".to_string(), index: 0 }]);
+    assert_eq!(analyses[0].suffixes, vec![AppliedRule { affix_type: AffixType::Suffix, flag: "Ss".to_string(), index: 0 }]);
+    assert_eq!(analyses[0].morph, vec!["st:book", "po:noun", "dp:neg", "is:pl"]);
+    assert_eq!(analyses[1].suffixes[0].index, 1);
+    assert_eq!(analyses[1].morph, vec!["st:book", "po:noun", "dp:neg", "is:3sg"]);
+
+    // An explicit stem field is kept as it is
+    let analyses = runmunch.analyze("go").unwrap();
+    assert_eq!(analyses.len(), 1);
+    assert!(analyses[0].prefixes.is_empty() && analyses[0].suffixes.is_empty());
+    assert_eq!(analyses[0].morph, vec!["st:went", "po:verb"]);
+
+    assert!(runmunch.analyze("unworks").unwrap().is_empty());
+}
+
+#[test]
+fn test_stemming() {
+    let affix_content = r#"SFX N Y 1
+SFX N y iness/S y ds:ness
+
+SFX S Y 2
+SFX S 0 es s is:pl
+SFX S 0 s [^s] is:pl
+"#;
+    let dict_content = "4\nhappy/N\nrose/S\tpo:noun\nrose\tst:rise po:verb\nwent\tst:go\n";
+
+    std::fs::write("/tmp/test_stem.aff", affix_content).expect("Should write affix file");
+    std::fs::write("/tmp/test_stem.dic", dict_content).expect("Should write dict file");
+
+    let mut runmunch = Runmunch::new();
+    runmunch.load_affix_file("/tmp/test_stem.aff").expect("Should load affix file");
+    runmunch.load_dictionary("/tmp/test_stem.dic").expect("Should load dictionary");
+
+    // The derivational suffix stays part of the stem, the inflectional one does not
+    assert_eq!(runmunch.stem("happinesses").unwrap(), vec!["happiness"]);
+    assert_eq!(runmunch.stem("happiness").unwrap(), vec!["happiness"]);
+    assert_eq!(runmunch.stem("happy").unwrap(), vec!["happy"]);
+    // Explicit st: fields and homonyms
+    assert_eq!(runmunch.stem("went").unwrap(), vec!["go"]);
+    assert_eq!(runmunch.stem("rose").unwrap(), vec!["rose", "rise"]);
+    assert_eq!(runmunch.stem("roses").unwrap(), vec!["rose"]);
+    assert!(runmunch.stem("wents").unwrap().is_empty());
+}
+
+#[test]
+fn test_generation() {
+    let affix_content = r#"SFX A Y 4
+SFX A a e a is:gen_sg
+SFX A a i a is:nom_pl
+SFX A a a a is:gen_pl
+SFX A a ama a is:dat_pl
+
+SFX V Y 2
+SFX V 0 s . is:3sg
+SFX V 0 ed . is:past
+"#;
+    let dict_content = "6\nkuća/A\tpo:noun\nžena/A\tpo:noun\nwalk/V\nwork/V\ngo\tpo:verb\nwent\tpo:verb st:go is:past\n";
+
+    std::fs::write("/tmp/test_generate.aff", affix_content).expect("Should write affix file");
+    std::fs::write("/tmp/test_generate.dic", dict_content).expect("Should write dict file");
+
+    let mut runmunch = Runmunch::new();
+    runmunch.load_affix_file("/tmp/test_generate.aff").expect("Should load affix file");
+    runmunch.load_dictionary("/tmp/test_generate.dic").expect("Should load dictionary");
+
+    assert_eq!(runmunch.generate("kuća", &["is:gen_pl".to_string()]).unwrap(), vec!["kuća"]);
+    assert_eq!(runmunch.generate("kuća", &["is:dat_pl".to_string()]).unwrap(), vec!["kućama"]);
+    assert!(runmunch.generate("kuća", &["is:past".to_string()]).unwrap().is_empty());
+    assert_eq!(runmunch.generate("walk", &[]).unwrap(), vec!["walk", "walked", "walks"]);
+
+    assert_eq!(runmunch.generate_like("kuća", "ženi").unwrap(), vec!["kući"]);
+    assert_eq!(runmunch.generate_like("work", "walked").unwrap(), vec!["worked"]);
+    // The lemma itself is the form without affixes
+    assert_eq!(runmunch.generate_like("work", "walk").unwrap(), vec!["work"]);
+    assert!(runmunch.generate_like("unknown", "walked").unwrap().is_empty());
+
+    // Irregular forms are entries of their own, listed under the lemma with st:
+    assert_eq!(runmunch.generate("go", &["is:past".to_string()]).unwrap(), vec!["went"]);
+    assert_eq!(runmunch.generate("go", &[]).unwrap(), vec!["go", "went"]);
+    assert_eq!(runmunch.generate_like("go", "walked").unwrap(), vec!["went"]);
+    assert_eq!(runmunch.generate_like("walk", "went").unwrap(), vec!["walked"]);
+}
+
+#[test]
+fn test_derivation_provenance() {
+    let affix_content = r#"PFX U Y 1
+PFX U 0 un .
+
+SFX D Y 2
+SFX D 0 ed [^e]
+SFX D 0 d e
+
+SFX R N 2
+SFX R 0 er [^e]
+SFX R 0 r e
+"#;
+    let dict_content = "2\nwork/DUR\nbake/DR\n";
+
+    std::fs::write("/tmp/test_provenance.aff", affix_content).expect("Should write affix file");
+    std::fs::write("/tmp/test_provenance.dic", dict_content).expect("Should write dict file");
+
+    let mut runmunch = Runmunch::new();
+    runmunch.load_affix_file("/tmp/test_provenance.aff").expect("Should load affix file");
+    runmunch.load_dictionary("/tmp/test_provenance.dic").expect("Should load dictionary");
+
+    let forms = runmunch.unmunch_with_provenance().unwrap();
+    let describe = |form: &DerivedForm| -> String {
+        let rules: Vec<String> = form.rules.iter().map(|rule| rule.to_string()).collect();
+        format!("{} {} [{}] {}", form.word, form.entry.word, rules.join(" "), form.cross_product)
+    };
+    let described: Vec<String> = forms.iter().map(describe).collect();
+    assert_eq!(described, vec![
+        "unwork work [PFX:U:0] false",
+        "unworked work [SFX:D:0 PFX:U:0] true",
+        "work work [] false",
+        "worked work [SFX:D:0] false",
+        "worker work [SFX:R:0] false",
+        "bake bake [] false",
+        "baked bake [SFX:D:1] false",
+        "baker bake [SFX:R:1] false",
+    ]);
+
+    let forms = runmunch.find_base_with_provenance("baked").unwrap();
+    assert_eq!(forms.len(), 3);
+    assert!(forms.iter().all(|form| form.entry.word == "bake" && form.entry.flags == vec!["D", "R"]));
+
+    let forms = runmunch.expand_word_with_provenance("bake").unwrap();
+    let described: Vec<String> = forms.iter().map(describe).collect();
+    assert_eq!(described, vec!["bake bake [] false", "baked bake [SFX:D:1] false", "baker bake [SFX:R:1] false", "unbake bake [PFX:U:0] false"]);
+}
+
+#[test]
+fn test_cli_output_formats() {
+    use std::io::Write;
+    use std::process::{Command, Stdio};
+
+    let affix_content = "NEEDAFFIX N\n\nPFX U Y 1\nPFX U 0 un .\n\nSFX D Y 1\nSFX D 0 ed .\n";
+    let dict_content = "3\nwork/DU\ngo\tst:go\nsomeone/N\n";
+
+    std::fs::write("/tmp/test_formats.aff", affix_content).expect("Should write affix file");
+    std::fs::write("/tmp/test_formats.dic", dict_content).expect("Should write dict file");
+
+    let run = |args: &[&str], input: &str| -> String {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_runmunch"))
+            .args(args)
+            .args(["/tmp/test_formats.aff", "/tmp/test_formats.dic"])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .expect("Should start runmunch");
+        child.stdin.take().unwrap().write_all(input.as_bytes()).unwrap();
+        let output = child.wait_with_output().unwrap();
+        assert!(output.status.success());
+        String::from_utf8(output.stdout).unwrap()
+    };
+
+    let jsonl = run(&["--format", "jsonl"], "");
+    assert_eq!(jsonl.lines().next().unwrap(), r#"{"word":"unwork","base":"work","flags":["D","U"]}"#);
+    assert_eq!(jsonl.lines().last().unwrap(), r#"{"word":"go","base":"go","flags":[]}"#);
+
+    let tsv = run(&["--format", "tsv"], "");
+    assert!(tsv.lines().any(|line| line == "unworked\twork"));
+
+    let grouped = run(&["--format", "grouped"], "");
+    // Every entry gets a line, even one without forms of its own
+    assert_eq!(grouped, "work/DU\tunwork unworked work worked\ngo\tgo\nsomeone/N\t\n");
+
+    let grouped = run(&["--find-base", "--format", "grouped"], "worked\nxyz\n");
+    assert_eq!(grouped, "worked\tunwork unworked work worked\nxyz\t\n");
+
+    // Provenance shows the flags an AF alias stands for
+    std::fs::write("/tmp/test_formats_af.aff", format!("AF 1\nAF DU\n{}", affix_content)).expect("Should write affix file");
+    std::fs::write("/tmp/test_formats_af.dic", "1\nwork/1\n").expect("Should write dict file");
+    let output = Command::new(env!("CARGO_BIN_EXE_runmunch"))
+        .args(["--provenance", "/tmp/test_formats_af.aff", "/tmp/test_formats_af.dic"])
+        .output()
+        .expect("Should run runmunch");
+    let provenance = String::from_utf8(output.stdout).unwrap();
+    assert!(provenance.lines().any(|line| line == "worked\twork/DU\tSFX:D:0\t-"));
+
+    let jsonl = run(&["--expand", "--format", "jsonl", "--provenance"], "work\n");
+    assert!(jsonl.lines().any(|line| {
+        line == r#"{"word":"unworked","base":"work","flags":["D","U"],"rules":["SFX:D:0","PFX:U:0"],"cross_product":true}"#
+    }));
+}
+
+#[test]
+fn test_cli_compound_heads() {
+    std::fs::write("/tmp/test_heads.aff", "COMPOUNDFLAG X\n").expect("Should write affix file");
+    std::fs::write("/tmp/test_heads.dic", "3\nfoot/X\nball/X\nhand/X\n").expect("Should write dict file");
+    std::fs::write("/tmp/test_heads.txt", "ball\n").expect("Should write heads file");
+
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_runmunch"))
+        .args(["--compounds", "2", "--compound-heads", "/tmp/test_heads.txt", "/tmp/test_heads.aff", "/tmp/test_heads.dic"])
+        .output()
+        .expect("Should run runmunch");
+    assert!(output.status.success());
+    let words: Vec<String> = String::from_utf8(output.stdout).unwrap().lines().map(String::from).collect();
+    assert_eq!(words, vec!["foot", "ball", "hand", "ballball", "football", "handball"]);
+}
+
+#[test]
+fn test_cli_expand_numeric_aliases() {
+    use std::io::Write;
+    use std::process::{Command, Stdio};
+
+    // Alias 1 stands for the real flag 2, which must not be read as an alias again
+    let affix_content = "FLAG num\nAF 2\nAF 2\nAF 1,2\n\nSFX 1 Y 1\nSFX 1 0 s .\n\nSFX 2 Y 1\nSFX 2 0 ed .\n";
+    std::fs::write("/tmp/test_expand_af.aff", affix_content).expect("Should write affix file");
+    std::fs::write("/tmp/test_expand_af.dic", "1\ncat/1\n").expect("Should write dict file");
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_runmunch"))
+        .args(["-e", "/tmp/test_expand_af.aff", "/tmp/test_expand_af.dic"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("Should start runmunch");
+    child.stdin.take().unwrap().write_all(b"cat\n").unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "cat\ncated\n");
+}